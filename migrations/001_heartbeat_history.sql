@@ -0,0 +1,20 @@
+-- Heartbeat arrivals aggregated per device per minute and per hour (UTC)
+CREATE TABLE IF NOT EXISTS heartbeat_history_minute (
+    mac_address VARCHAR(17) NOT NULL,
+    bucket_start DATETIME NOT NULL,
+    heartbeat_count INT UNSIGNED NOT NULL,
+    first_seen DATETIME NOT NULL,
+    last_seen DATETIME NOT NULL,
+    PRIMARY KEY (mac_address, bucket_start),
+    KEY idx_heartbeat_history_minute_bucket (bucket_start)
+);
+
+CREATE TABLE IF NOT EXISTS heartbeat_history_hour (
+    mac_address VARCHAR(17) NOT NULL,
+    bucket_start DATETIME NOT NULL,
+    heartbeat_count INT UNSIGNED NOT NULL,
+    first_seen DATETIME NOT NULL,
+    last_seen DATETIME NOT NULL,
+    PRIMARY KEY (mac_address, bucket_start),
+    KEY idx_heartbeat_history_hour_bucket (bucket_start)
+);
@@ -0,0 +1,4 @@
+-- Soft deactivation for the device registry API
+ALTER TABLE devices ADD COLUMN deactivated_at DATETIME NULL;
+
+CREATE INDEX idx_devices_zone_camera ON devices (zone_number, camera_number);
@@ -0,0 +1,10 @@
+-- Per-account overrides for multi-tenant fleets
+CREATE TABLE IF NOT EXISTS account_settings (
+    account_id INT NOT NULL PRIMARY KEY,
+    redirect_url VARCHAR(2048) NULL,
+    heartbeat_interval INT UNSIGNED NULL,
+    rate_limit_per_minute INT UNSIGNED NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_devices_account ON devices (account_id);
@@ -0,0 +1,14 @@
+-- Squelches set through the admin API, optionally limited to a time window
+CREATE TABLE IF NOT EXISTS squelches (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    scope ENUM('device', 'zone', 'account') NOT NULL,
+    mac_address VARCHAR(17) NULL,
+    zone_number INT NULL,
+    account_id INT NULL,
+    starts_at DATETIME NULL,
+    ends_at DATETIME NULL,
+    reason VARCHAR(1024) NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    lifted_at DATETIME NULL,
+    KEY idx_squelches_active (lifted_at, ends_at)
+);
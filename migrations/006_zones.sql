@@ -0,0 +1,11 @@
+-- Zones as first-class entities; cameras are the devices sharing a zone_number
+CREATE TABLE IF NOT EXISTS zones (
+    zone_number INT NOT NULL PRIMARY KEY,
+    account_id INT NULL,
+    name VARCHAR(255) NOT NULL,
+    site VARCHAR(255) NULL,
+    expected_camera_count INT UNSIGNED NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_modified DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    KEY idx_zones_account (account_id)
+);
@@ -0,0 +1,11 @@
+-- Events written in the same transaction as the device update they describe.
+-- The relay publishes unpublished rows in id order and stamps published_at.
+CREATE TABLE IF NOT EXISTS event_outbox (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    event_kind VARCHAR(32) NOT NULL,
+    account_id INT NULL,
+    payload TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    published_at DATETIME NULL,
+    KEY idx_event_outbox_unpublished (published_at, id)
+);
@@ -0,0 +1,16 @@
+-- Maintenance windows: one-off (starts_at/ends_at) or recurring (cron schedule
+-- plus duration, optionally bounded by starts_at/ends_at). Times are UTC.
+CREATE TABLE IF NOT EXISTS maintenance_windows (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    scope ENUM('device', 'zone', 'account') NOT NULL,
+    mac_address VARCHAR(17) NULL,
+    zone_number INT NULL,
+    account_id INT NULL,
+    starts_at DATETIME NULL,
+    ends_at DATETIME NULL,
+    schedule VARCHAR(255) NULL,
+    duration_minutes INT UNSIGNED NULL,
+    reason VARCHAR(1024) NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    KEY idx_maintenance_windows_ends (ends_at)
+);
@@ -0,0 +1,4 @@
+-- Expected heartbeat interval per device and per zone, in seconds.
+-- A device uses its own interval, then its zone's, then its account's.
+ALTER TABLE devices ADD COLUMN heartbeat_interval INT UNSIGNED NULL;
+ALTER TABLE zones ADD COLUMN heartbeat_interval INT UNSIGNED NULL;
@@ -0,0 +1,11 @@
+-- Latest telemetry reported by each device, written when it changes
+CREATE TABLE IF NOT EXISTS device_telemetry (
+    mac_address VARCHAR(17) NOT NULL PRIMARY KEY,
+    firmware_version VARCHAR(64) NULL,
+    uptime_seconds BIGINT UNSIGNED NULL,
+    cpu_temperature FLOAT NULL,
+    free_storage_bytes BIGINT UNSIGNED NULL,
+    rssi SMALLINT NULL,
+    reported_at DATETIME NOT NULL,
+    KEY idx_device_telemetry_firmware (firmware_version)
+);
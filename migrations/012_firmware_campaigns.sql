@@ -0,0 +1,29 @@
+-- Staged firmware rollouts. A campaign offers firmware_url to `percentage` percent
+-- of the devices in its zones (all zones when zone_numbers is NULL) of its account
+-- (every account when NULL) that report a firmware version other than the target.
+CREATE TABLE IF NOT EXISTS firmware_campaigns (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    firmware_version VARCHAR(64) NOT NULL,
+    firmware_url VARCHAR(2048) NOT NULL,
+    account_id INT NULL,
+    zone_numbers VARCHAR(1024) NULL,
+    percentage TINYINT UNSIGNED NOT NULL,
+    max_offline_percent TINYINT UNSIGNED NOT NULL,
+    status ENUM('active', 'paused', 'completed', 'cancelled') NOT NULL DEFAULT 'active',
+    paused_reason VARCHAR(1024) NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    KEY idx_firmware_campaigns_status (status)
+);
+
+-- Devices a campaign has offered its firmware to, and when they came back on it
+CREATE TABLE IF NOT EXISTS firmware_campaign_devices (
+    campaign_id BIGINT UNSIGNED NOT NULL,
+    mac_address VARCHAR(17) NOT NULL,
+    previous_version VARCHAR(64) NULL,
+    offered_at DATETIME NOT NULL,
+    upgraded_at DATETIME NULL,
+    PRIMARY KEY (campaign_id, mac_address),
+    KEY idx_firmware_campaign_devices_mac (mac_address)
+);
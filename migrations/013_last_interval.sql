@@ -0,0 +1,3 @@
+-- Heartbeat interval last returned to each device, in seconds, so staleness
+-- checks in SQL use the same interval as the heartbeat cache
+ALTER TABLE devices ADD COLUMN last_interval INT UNSIGNED NULL;
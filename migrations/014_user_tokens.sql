@@ -0,0 +1,6 @@
+-- API tokens of operator accounts, stored as SHA-256 hashes. Users with an
+-- account_id act on that account only, users without one on the whole fleet.
+ALTER TABLE users
+    ADD COLUMN account_id INT NULL,
+    ADD COLUMN token_hash CHAR(64) NULL,
+    ADD UNIQUE KEY uq_users_token_hash (token_hash);
@@ -0,0 +1,4 @@
+-- Outbox rows the relay cannot read are set aside instead of blocking or vanishing
+ALTER TABLE event_outbox
+    ADD COLUMN dead_lettered_at DATETIME NULL,
+    ADD COLUMN error VARCHAR(1024) NULL;
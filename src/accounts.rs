@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use mysql::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::server::AppState;
+
+/// How long loaded account settings are reused before MySQL is read again
+const SETTINGS_TTL: Duration = Duration::from_secs(60);
+
+/// Per-account overrides; unset fields keep hbd's defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountSettings {
+    /// Redirect target returned to squelched devices of this account
+    pub redirect_url: Option<String>,
+    /// Heartbeat interval in seconds returned to devices
+    pub heartbeat_interval: Option<u32>,
+    /// Maximum heartbeats per minute accepted across the whole account
+    pub rate_limit_per_minute: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountSettingsResponse {
+    pub account_id: i32,
+    #[serde(flatten)]
+    pub settings: AccountSettings,
+}
+
+struct CachedSettings {
+    settings: AccountSettings,
+    loaded_at: Instant,
+}
+
+/// Account settings cache and per-account heartbeat rate limiting
+pub struct AccountRegistry {
+    settings: Mutex<HashMap<i32, CachedSettings>>,
+    /// (minute, heartbeats accepted in that minute) per account
+    rate_windows: Mutex<HashMap<i32, (i64, u32)>>,
+}
+
+impl AccountRegistry {
+    pub fn new() -> Self {
+        Self {
+            settings: Mutex::new(HashMap::new()),
+            rate_windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Settings for an account, re-read from MySQL once older than SETTINGS_TTL.
+    /// While MySQL is unavailable the last loaded settings stay in effect.
+    pub fn settings(&self, state: &AppState, account_id: i32) -> AccountSettings {
+        if let Some(cached) = self.settings.lock().unwrap().get(&account_id)
+            && cached.loaded_at.elapsed() < SETTINGS_TTL
+        {
+            return cached.settings.clone();
+        }
+
+        let settings = match load_settings(state, account_id) {
+            Ok(settings) => settings.unwrap_or_default(),
+            Err(e) => {
+                log::debug!("Keeping cached settings for account {}: {:#}", account_id, e);
+                self.settings.lock().unwrap().get(&account_id)
+                    .map(|cached| cached.settings.clone())
+                    .unwrap_or_default()
+            }
+        };
+        self.settings.lock().unwrap().insert(account_id, CachedSettings {
+            settings: settings.clone(),
+            loaded_at: Instant::now(),
+        });
+        settings
+    }
+
+    /// Forget cached settings so the next heartbeat re-reads them
+    pub fn invalidate(&self, account_id: i32) {
+        self.settings.lock().unwrap().remove(&account_id);
+    }
+
+    /// Count a heartbeat against the account's per-minute limit.
+    /// Returns false once `limit` heartbeats have been accepted this minute.
+    pub fn allow_heartbeat(&self, account_id: i32, limit: u32, now: DateTime<Utc>) -> bool {
+        let minute = now.timestamp().div_euclid(60);
+        let mut windows = self.rate_windows.lock().unwrap();
+        let window = windows.entry(account_id).or_insert((minute, 0));
+        if window.0 != minute {
+            *window = (minute, 0);
+        }
+        if window.1 >= limit {
+            return false;
+        }
+        window.1 += 1;
+        true
+    }
+}
+
+fn load_settings(state: &AppState, account_id: i32) -> anyhow::Result<Option<AccountSettings>> {
+    let mut conn = state.get_read_connection()?;
+    Ok(find_settings(&mut conn, account_id)?)
+}
+
+fn find_settings(conn: &mut mysql::PooledConn, account_id: i32) -> mysql::Result<Option<AccountSettings>> {
+    let row: Option<(Option<String>, Option<u32>, Option<u32>)> = conn.exec_first(
+        "SELECT redirect_url, heartbeat_interval, rate_limit_per_minute FROM account_settings WHERE account_id = ?",
+        (account_id,),
+    )?;
+    Ok(row.map(|(redirect_url, heartbeat_interval, rate_limit_per_minute)| AccountSettings {
+        redirect_url,
+        heartbeat_interval,
+        rate_limit_per_minute,
+    }))
+}
+
+/// Get an account's settings; accounts without overrides return all fields null
+pub async fn get_account_settings(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(account_id): Path<i32>,
+) -> Result<Json<AccountSettingsResponse>, ApiError> {
+    principal.authorize(Some(account_id))?;
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let settings = find_settings(&mut conn, account_id)
+        .map_err(|e| ApiError::database("Failed to read account settings", e))?
+        .unwrap_or_default();
+    Ok(Json(AccountSettingsResponse { account_id, settings }))
+}
+
+/// Replace an account's settings
+pub async fn update_account_settings(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(account_id): Path<i32>,
+    Json(settings): Json<AccountSettings>,
+) -> Result<Json<AccountSettingsResponse>, ApiError> {
+    principal.authorize(Some(account_id))?;
+    if settings.heartbeat_interval == Some(0) || settings.rate_limit_per_minute == Some(0) {
+        return Err(ApiError::bad_request("heartbeat_interval and rate_limit_per_minute must be positive"));
+    }
+    if let Some(url) = &settings.redirect_url
+        && !(url.starts_with("http://") || url.starts_with("https://"))
+    {
+        return Err(ApiError::bad_request("redirect_url must be an http(s) URL"));
+    }
+
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    conn.exec_drop(
+        "INSERT INTO account_settings (account_id, redirect_url, heartbeat_interval, rate_limit_per_minute)
+         VALUES (?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE
+            redirect_url = VALUES(redirect_url),
+            heartbeat_interval = VALUES(heartbeat_interval),
+            rate_limit_per_minute = VALUES(rate_limit_per_minute)",
+        (account_id, &settings.redirect_url, settings.heartbeat_interval, settings.rate_limit_per_minute),
+    ).map_err(|e| ApiError::database("Failed to update account settings", e))?;
+
+    state.accounts.invalidate(account_id);
+    log::info!("Settings for account {} updated: {:?}", account_id, settings);
+    Ok(Json(AccountSettingsResponse { account_id, settings }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_rate_limit_resets_each_minute() {
+        let registry = AccountRegistry::new();
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 5).unwrap();
+        assert!(registry.allow_heartbeat(1, 2, now));
+        assert!(registry.allow_heartbeat(1, 2, now));
+        assert!(!registry.allow_heartbeat(1, 2, now));
+        // Other accounts have their own budget
+        assert!(registry.allow_heartbeat(2, 2, now));
+        assert!(registry.allow_heartbeat(1, 2, now + chrono::Duration::seconds(60)));
+    }
+}
@@ -0,0 +1,314 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+use crate::cache::{FlapChange, HeartbeatCache};
+use crate::config::AlertsConfig;
+use crate::events::{DeviceEvent, EventKind};
+use crate::server::AppState;
+
+/// A device's state as seen by one outage check
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub mac_address: String,
+    pub account_id: Option<i32>,
+    pub zone_number: Option<i32>,
+    pub global_ip_address: String,
+    pub online: bool,
+}
+
+/// A group of devices that fail together: a zone, or everything behind one public IP
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SiteKey {
+    Zone(i32),
+    PublicIp(String),
+}
+
+struct SiteOutage {
+    /// Outage fully covered by a zone outage, so no separate events are sent for it
+    silent: bool,
+}
+
+/// Turns per-device online/offline observations into events, collapsing
+/// simultaneous outages of most of a site into one site-level event
+pub struct OutageMonitor {
+    threshold: f64,
+    min_devices: usize,
+    group_by_public_ip: bool,
+    /// Last observed online state per device
+    online: HashMap<String, bool>,
+    outages: HashMap<SiteKey, SiteOutage>,
+    /// Devices whose offline transition was folded into a site event
+    suppressed: HashSet<String>,
+    flap_threshold: usize,
+    flap_window: ChronoDuration,
+    /// Holds each device's transition history and flapping state
+    cache: HeartbeatCache<'static>,
+}
+
+impl OutageMonitor {
+    pub fn new(config: &AlertsConfig, cache: HeartbeatCache<'static>) -> Self {
+        Self {
+            threshold: config.site_offline_threshold,
+            min_devices: config.site_min_devices.max(1),
+            group_by_public_ip: config.group_by_public_ip,
+            online: HashMap::new(),
+            outages: HashMap::new(),
+            suppressed: HashSet::new(),
+            flap_threshold: config.flap_threshold,
+            flap_window: ChronoDuration::seconds(config.flap_window as i64),
+            cache,
+        }
+    }
+
+    fn site_keys(&self, observation: &Observation) -> Vec<SiteKey> {
+        let mut keys = Vec::new();
+        if let Some(zone) = observation.zone_number {
+            keys.push(SiteKey::Zone(zone));
+        }
+        if self.group_by_public_ip && !observation.global_ip_address.is_empty() {
+            keys.push(SiteKey::PublicIp(observation.global_ip_address.clone()));
+        }
+        keys
+    }
+
+    fn site_event(kind: EventKind, key: &SiteKey, members: &[&Observation], devices: Vec<String>, at: DateTime<Utc>) -> DeviceEvent {
+        let account_id = members.first().and_then(|first| first.account_id)
+            .filter(|account| members.iter().all(|member| member.account_id == Some(*account)));
+        DeviceEvent {
+            kind,
+            account_id,
+            mac_address: None,
+            zone_number: match key { SiteKey::Zone(zone) => Some(*zone), _ => None },
+            global_ip_address: match key { SiteKey::PublicIp(ip) => Some(ip.clone()), _ => None },
+            devices,
+            details: serde_json::Value::Null,
+            at,
+        }
+    }
+
+    /// Compare a full snapshot of devices with the previous one and return the events to publish
+    pub fn evaluate(&mut self, observations: &[Observation], now: DateTime<Utc>) -> Vec<DeviceEvent> {
+        let mut events = Vec::new();
+        let mut sites: BTreeMap<SiteKey, Vec<&Observation>> = BTreeMap::new();
+        for observation in observations {
+            for key in self.site_keys(observation) {
+                sites.entry(key).or_default().push(observation);
+            }
+        }
+
+        // Site outages first; zones sort before public IPs so IP outages can defer to them
+        let mut restored = HashSet::new();
+        for (key, members) in &sites {
+            let offline: Vec<&Observation> = members.iter().copied().filter(|member| !member.online).collect();
+            let down = members.len() >= self.min_devices
+                && offline.len() as f64 >= self.threshold * members.len() as f64;
+            match (down, self.outages.get(key)) {
+                (true, None) => {
+                    let silent = matches!(key, SiteKey::PublicIp(_)) && offline.iter().all(|member| {
+                        member.zone_number.is_some_and(|zone| self.outages.contains_key(&SiteKey::Zone(zone)))
+                    });
+                    let devices = offline.iter().map(|member| member.mac_address.clone()).collect();
+                    if silent {
+                        log::debug!("Site outage {:?} is covered by zone outages", key);
+                    } else {
+                        events.push(Self::site_event(EventKind::SiteOffline, key, members, devices, now));
+                    }
+                    self.outages.insert(key.clone(), SiteOutage { silent });
+                },
+                (false, Some(outage)) => {
+                    if !outage.silent {
+                        let devices = members.iter().map(|member| member.mac_address.clone()).collect();
+                        events.push(Self::site_event(EventKind::SiteRestored, key, members, devices, now));
+                    }
+                    self.outages.remove(key);
+                    restored.insert(key.clone());
+                },
+                _ => {},
+            }
+        }
+        // Sites whose devices have all left the cache are forgotten without an event
+        self.outages.retain(|key, _| sites.contains_key(key));
+
+        let mut seen = HashSet::new();
+        for observation in observations {
+            seen.insert(observation.mac_address.clone());
+            let previous = self.online.insert(observation.mac_address.clone(), observation.online);
+            let keys = self.site_keys(observation);
+            if keys.iter().any(|key| self.outages.contains_key(key)) {
+                if previous == Some(true) && !observation.online {
+                    self.suppressed.insert(observation.mac_address.clone());
+                }
+                continue;
+            }
+
+            if keys.iter().any(|key| restored.contains(key)) && self.suppressed.remove(&observation.mac_address) {
+                // The site came back but this device did not; report it on its own
+                if !observation.online {
+                    events.push(self.device_event(EventKind::Offline, observation, now));
+                }
+                continue;
+            }
+
+            let mac = &observation.mac_address;
+            match previous {
+                Some(was_online) if was_online != observation.online => {
+                    match self.cache.record_transition(mac, now, self.flap_window, self.flap_threshold) {
+                        FlapChange::Started => events.push(self.flap_event(EventKind::FlappingStarted, observation, now)),
+                        _ if self.cache.is_flapping(mac) => log::debug!("Suppressing transition of flapping device {}", mac),
+                        _ => {
+                            let kind = if observation.online { EventKind::Online } else { EventKind::Offline };
+                            events.push(self.device_event(kind, observation, now));
+                        },
+                    }
+                },
+                _ => {
+                    if self.cache.settle_flapping(mac, now, self.flap_window, self.flap_threshold) == FlapChange::Stopped {
+                        events.push(self.flap_event(EventKind::FlappingStopped, observation, now));
+                    }
+                },
+            }
+        }
+        self.online.retain(|mac, _| seen.contains(mac));
+        self.suppressed.retain(|mac| seen.contains(mac));
+        self.cache.retain_flaps(&seen);
+        events
+    }
+
+    /// Flapping events carry the current state, since the transitions themselves are not reported
+    fn flap_event(&self, kind: EventKind, observation: &Observation, at: DateTime<Utc>) -> DeviceEvent {
+        self.device_event(kind, observation, at).with_details(serde_json::json!({
+            "online": observation.online,
+            "transitions": self.cache.transition_count(&observation.mac_address),
+            "window": self.flap_window.num_seconds()
+        }))
+    }
+
+    fn device_event(&self, kind: EventKind, observation: &Observation, at: DateTime<Utc>) -> DeviceEvent {
+        DeviceEvent::device(
+            kind,
+            &observation.mac_address,
+            observation.account_id,
+            observation.zone_number,
+            Some(observation.global_ip_address.clone()),
+            at,
+        )
+    }
+}
+
+/// Background task: check cached devices for online/offline transitions and record events in the outbox
+pub async fn run_outage_monitor(state: AppState, interval: Duration) {
+    let mut monitor = OutageMonitor::new(&state.config.alerts, state.heart_beat_cache.clone());
+    let offline_after = state.config.history.offline_after;
+    loop {
+        tokio::time::sleep(interval).await;
+        let now = Utc::now();
+        let observations: Vec<Observation> = state.heart_beat_cache.devices().into_iter()
+            .map(|device| Observation {
+                online: now - device.last_heartbeat
+                    < ChronoDuration::seconds(crate::intervals::stale_after(Some(device.interval), offline_after) as i64),
+                mac_address: device.mac_address.to_uppercase(),
+                account_id: device.account_id,
+                zone_number: device.zone_number,
+                global_ip_address: device.global_ip_address,
+            })
+            .collect();
+        let events = monitor.evaluate(&observations, now);
+        if events.is_empty() {
+            continue;
+        }
+        let emit_state = state.clone();
+        let emitted = tokio::task::spawn_blocking(move || {
+            for event in events {
+                crate::outbox::emit(&emit_state, event);
+            }
+        }).await;
+        if let Err(e) = emitted {
+            log::error!("Outage event task panicked: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AlertsConfig {
+        AlertsConfig {
+            site_offline_threshold: 0.75,
+            site_min_devices: 3,
+            ..AlertsConfig::default()
+        }
+    }
+
+    fn observe(macs: &[&str], offline: &[&str]) -> Vec<Observation> {
+        macs.iter().map(|mac| Observation {
+            mac_address: mac.to_string(),
+            account_id: Some(1),
+            zone_number: Some(5),
+            global_ip_address: "203.0.113.7".to_string(),
+            online: !offline.contains(mac),
+        }).collect()
+    }
+
+    fn kinds(events: &[DeviceEvent]) -> Vec<EventKind> {
+        events.iter().map(|event| event.kind).collect()
+    }
+
+    #[test]
+    fn test_single_device_outage_is_reported_individually() {
+        let mut monitor = OutageMonitor::new(&config(), HeartbeatCache::new());
+        let macs = ["A", "B", "C", "D"];
+        let now = Utc::now();
+        assert!(monitor.evaluate(&observe(&macs, &[]), now).is_empty());
+        assert_eq!(kinds(&monitor.evaluate(&observe(&macs, &["A"]), now)), vec![EventKind::Offline]);
+        assert_eq!(kinds(&monitor.evaluate(&observe(&macs, &[]), now)), vec![EventKind::Online]);
+    }
+
+    #[test]
+    fn test_site_outage_collapses_device_events() {
+        let mut monitor = OutageMonitor::new(&config(), HeartbeatCache::new());
+        let macs = ["A", "B", "C", "D"];
+        let now = Utc::now();
+        monitor.evaluate(&observe(&macs, &[]), now);
+
+        // Zone and public IP outage coincide: one event, no per-device events
+        let events = monitor.evaluate(&observe(&macs, &macs), now);
+        assert_eq!(kinds(&events), vec![EventKind::SiteOffline]);
+        assert_eq!(events[0].zone_number, Some(5));
+        assert_eq!(events[0].devices.len(), 4);
+        assert_eq!(events[0].account_id, Some(1));
+
+        // Power returns to all but one camera
+        let events = monitor.evaluate(&observe(&macs, &["D"]), now);
+        assert_eq!(kinds(&events), vec![EventKind::SiteRestored, EventKind::Offline]);
+        assert_eq!(events[1].mac_address.as_deref(), Some("D"));
+    }
+
+    #[test]
+    fn test_flapping_device_transitions_are_suppressed() {
+        let config = AlertsConfig { flap_threshold: 4, flap_window: 600, ..config() };
+        let cache = HeartbeatCache::new();
+        let mut monitor = OutageMonitor::new(&config, cache.clone());
+        let macs = ["A", "B", "C", "D"];
+        let start = Utc::now();
+        let at = |minute: i64| start + ChronoDuration::minutes(minute);
+        monitor.evaluate(&observe(&macs, &[]), at(0));
+
+        let mut seen = Vec::new();
+        for minute in 1..=6 {
+            let offline: &[&str] = if minute % 2 == 1 { &["A"] } else { &[] };
+            seen.extend(kinds(&monitor.evaluate(&observe(&macs, offline), at(minute))));
+        }
+        assert_eq!(seen, vec![
+            EventKind::Offline, EventKind::Online, EventKind::Offline, EventKind::FlappingStarted,
+        ]);
+        assert!(cache.is_flapping("A"));
+
+        // Quiet for a full window: flapping ends, reporting the state the device settled in
+        let events = monitor.evaluate(&observe(&macs, &[]), at(20));
+        assert_eq!(kinds(&events), vec![EventKind::FlappingStopped]);
+        assert_eq!(events[0].details["online"], true);
+        assert!(!cache.is_flapping("A"));
+    }
+}
@@ -4,36 +4,40 @@ use axum::{
     response::Json,
 };
 use mysql::prelude::*;
+use mysql::TxOpts;
 use serde_json::json;
 use std::collections::HashMap;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::time::Duration;
 
 use crate::server::{AppState, HeartbeatQuery, HeartbeatDevice};
+use crate::replicas::ReadConnection;
 use crate::cache::{HeartbeatCache, HeartbeatCacheInfo};
+use crate::intervals;
+use crate::events::{DeviceEvent, EventKind};
+use crate::metrics::Counter;
+use crate::stream::LiveHeartbeat;
+use crate::telemetry::Telemetry;
+use crate::write_queue::PendingWrite;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Minimum time between last_heartbeat writes for an unchanged device
+const HEARTBEAT_WRITE_INTERVAL_SECS: i64 = 60;
 
 struct AuthorizedResult{
     authorized: bool,
     squelched: bool,
+    account_id: Option<i32>,
+    zone_number: Option<i32>,
+    /// Device or zone heartbeat interval from the registry
+    heartbeat_interval: Option<u32>,
 }
 
 fn get_pip()-> String{
     return "127.1.1.0".to_string();
 }
 
-fn get_status_code() -> Result<Json<serde_json::Value>,StatusCode>{
-    let mut result:StatusCode  = StatusCode::OK;
-
-    match result{
-        StatusCode::OK =>{
-            Ok(Json(serde_json::json!({
-                "status": "success"
-                })))
-         },
-        _ => Err(StatusCode::INTERNAL_SERVER_ERROR)
-    }
-    
-}
 /// is mac in cache or db
 fn get_authorized(state: &AppState, heartbeat_cache: &HeartbeatCache, mac: &str) -> Result<AuthorizedResult, StatusCode>{
     match heartbeat_cache.get_device(mac){
@@ -41,7 +45,13 @@ fn get_authorized(state: &AppState, heartbeat_cache: &HeartbeatCache, mac: &str)
             //call db to get auth and squelched.
             call_is_device_active(state, mac)
         },
-        Some(_)=>  Ok(AuthorizedResult { authorized: true, squelched: false })
+        Some(cached_device)=>  Ok(AuthorizedResult {
+            authorized: true,
+            squelched: cached_device.squelched,
+            account_id: cached_device.account_id,
+            zone_number: cached_device.zone_number,
+            heartbeat_interval: cached_device.registry_interval,
+        })
     }
 
 }
@@ -55,8 +65,8 @@ fn get_last_heartbeat_write(heartbeat_cache: &HeartbeatCache ,mac: &str) -> Opti
 } 
 
 pub fn call_is_device_active(state: &AppState, mac: &str) -> Result<AuthorizedResult, StatusCode>  {
-    // Call the stored procedure
-    match state.get_connection() {
+    // Call the stored procedure; lookups can be served by a read replica
+    match state.get_read_connection() {
         Ok(mut conn) => {
             let result: Result<Vec<mysql::Row>, mysql::Error> = conn.exec(
                 "CALL is_device_active(?, @msg)",
@@ -69,23 +79,42 @@ pub fn call_is_device_active(state: &AppState, mac: &str) -> Result<AuthorizedRe
             match result {
                 Ok(mut rows) => {
                     if let Some(row) = rows.pop() {
-                        let (_account_id, squelch): (Option<i32>, i32) = mysql::from_row(row);
+                        let (account_id, squelch): (Option<i32>, i32) = mysql::from_row(row);
+                        // Devices deactivated through the registry API are refused. Without the
+                        // registry the heartbeat is refused too, rather than let such a device in.
+                        let registry = crate::devices::registry_status(&mut conn, mac).map_err(|e| {
+                            log::warn!("Registry lookup failed for {}: {}", mac, e);
+                            lookup_unavailable(state, &conn, &e)
+                        })?;
                         Ok(AuthorizedResult {
-                            authorized: true,
+                            authorized: !registry.deactivated,
                             squelched: squelch != 0,
+                            account_id,
+                            zone_number: registry.zone_number,
+                            heartbeat_interval: registry.heartbeat_interval,
                         })
                     } else {
                         Ok(AuthorizedResult {
                             authorized: false,
                             squelched: true,
+                            account_id: None,
+                            zone_number: None,
+                            heartbeat_interval: None,
                         })
                     }
                 },
-                Err(_) => {
+                Err(mysql::Error::MySqlError(_)) => {
                     Ok(AuthorizedResult {
                         authorized: false,
                         squelched: true,
+                        account_id: None,
+                        zone_number: None,
+                        heartbeat_interval: None,
                     })
+                },
+                Err(e) => {
+                    log::warn!("is_device_active failed for {}: {}", mac, e);
+                    Err(lookup_unavailable(state, &conn, &e))
                 }
             }
         },
@@ -94,56 +123,478 @@ pub fn call_is_device_active(state: &AppState, mac: &str) -> Result<AuthorizedRe
         }
     }
 }
+
+/// A lookup that lost its connection mid-call counts against the replica it ran on,
+/// or the breaker for the primary; the heartbeat is refused as unavailable either way
+fn lookup_unavailable(state: &AppState, conn: &ReadConnection, e: &mysql::Error) -> StatusCode {
+    if !matches!(e, mysql::Error::MySqlError(_)) {
+        match &conn.replica {
+            Some(endpoint) => state.replicas.mark_unhealthy(endpoint),
+            None => state.db_breaker.record_failure(),
+        }
+    }
+    StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// The last_heartbeat stored for a device, in UTC
+fn stored_heartbeat_time<Q: Queryable>(conn: &mut Q, mac_address: &str) -> mysql::Result<Option<DateTime<Utc>>> {
+    let stored: Option<Option<String>> = conn.exec_first(
+        "SELECT DATE_FORMAT(CONVERT_TZ(last_heartbeat, @@session.time_zone, '+00:00'), '%Y-%m-%d %H:%i:%s')
+         FROM devices WHERE mac_address = UPPER(?)",
+        (mac_address,),
+    )?;
+    Ok(stored.flatten()
+        .and_then(|stored| NaiveDateTime::parse_from_str(&stored, DB_DATETIME_FORMAT).ok())
+        .map(|stored| stored.and_utc()))
+}
+
+/// Whether a replayed heartbeat received at `received_at` is still the latest one.
+/// Heartbeats written directly since recovery are newer, and their addresses win.
+fn replay_is_current(stored: Option<DateTime<Utc>>, received_at: DateTime<Utc>) -> bool {
+    stored.is_none_or(|stored| stored <= received_at)
+}
+
+/// Store the interval a device was given, which SQL staleness checks read
+fn record_interval<Q: Queryable>(conn: &mut Q, mac_address: &str, interval: Option<u64>) -> mysql::Result<()> {
+    match interval {
+        Some(interval) => conn.exec_drop(
+            "UPDATE devices SET last_interval = ? WHERE mac_address = UPPER(?)",
+            (interval, mac_address),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Apply a heartbeat write to MySQL.
+/// Replayed writes restore the original arrival time of the heartbeat, or leave the
+/// device alone when it has reported since.
+pub fn apply_write<Q: Queryable>(conn: &mut Q, write: &PendingWrite, replay: bool) -> mysql::Result<()> {
+    match write {
+        PendingWrite::Heartbeat { mac_address, local_ip_address, global_ip_address, received_at, telemetry, interval } => {
+            // A replay older than what is stored would move the device's addresses,
+            // last_heartbeat and interval backwards, so only its telemetry is kept
+            let current = !replay || replay_is_current(stored_heartbeat_time(conn, mac_address)?, *received_at);
+            if current {
+                conn.exec_drop(
+                    "CALL set_device_last_heartbeat(?, ?, ?, @msg, @prev_ip)",
+                    (mac_address, local_ip_address, global_ip_address)
+                )?;
+                // The procedure stamps NOW(); a replay restores when the heartbeat arrived
+                if replay {
+                    conn.exec_drop(
+                        "UPDATE devices SET last_heartbeat = CONVERT_TZ(?, '+00:00', @@session.time_zone) WHERE mac_address = UPPER(?)",
+                        (received_at.format(DB_DATETIME_FORMAT).to_string(), mac_address)
+                    )?;
+                }
+                record_interval(conn, mac_address, *interval)?;
+            }
+            if let Some(telemetry) = telemetry {
+                crate::telemetry::persist(conn, mac_address, telemetry, *received_at)?;
+            }
+        },
+        PendingWrite::ReadyDevice { mac_address, local_ip_address, global_ip_address, received_at, telemetry, interval } => {
+            conn.exec_drop(
+                "CALL set_ready_device(?, ?, ?)",
+                (mac_address, local_ip_address, global_ip_address)
+            )?;
+            record_interval(conn, mac_address, *interval)?;
+            if let Some(telemetry) = telemetry {
+                crate::telemetry::persist(conn, mac_address, telemetry, *received_at)?;
+            }
+        },
+        PendingWrite::Event { event } => crate::outbox::insert(conn, event)?,
+        PendingWrite::FirmwareOffer { campaign_id, mac_address, previous_version, offered_at } => {
+            crate::firmware::persist_offer(conn, *campaign_id, mac_address, previous_version, *offered_at)?;
+        },
+        PendingWrite::FirmwareUpgrade { campaign_id, mac_address, upgraded_at } => {
+            crate::firmware::persist_upgrade(conn, *campaign_id, mac_address, *upgraded_at)?;
+        },
+    }
+    Ok(())
+}
+
+/// Write through to MySQL together with the events it produced, in one transaction,
+/// or journal and queue the write and its events if the database is unavailable.
+/// Returns true if the write reached the database.
+pub fn write_or_queue(state: &AppState, write: PendingWrite, events: Vec<DeviceEvent>) -> bool {
+    // get_connection counts its own failures against the breaker; count the write's here
+    let result = state.get_connection().and_then(|mut conn| {
+        apply_with_events(&mut conn, &write, &events)
+            .inspect_err(|_| state.db_breaker.record_failure())
+            .map_err(anyhow::Error::from)
+    });
+    match result {
+        Ok(()) => {
+            state.db_breaker.record_success();
+            if !events.is_empty() || matches!(write, PendingWrite::Event { .. }) {
+                state.outbox.notify();
+            }
+            true
+        },
+        Err(e) => {
+            log::warn!("Queueing write for {}: {:#}", write.mac_address(), e);
+            queue_write(state, write);
+            for event in events {
+                queue_write(state, PendingWrite::Event { event });
+            }
+            false
+        }
+    }
+}
+
+fn apply_with_events(conn: &mut mysql::PooledConn, write: &PendingWrite, events: &[DeviceEvent]) -> mysql::Result<()> {
+    let mut tx = conn.start_transaction(TxOpts::default())?;
+    apply_write(&mut tx, write, false)?;
+    for event in events {
+        crate::outbox::insert(&mut tx, event)?;
+    }
+    tx.commit()
+}
+
+fn queue_write(state: &AppState, write: PendingWrite) {
+    let journal_seq = state.journal.as_ref().and_then(|journal| {
+        journal.append(&write)
+            .map_err(|e| log::error!("Failed to journal write for {}: {:#}", write.mac_address(), e))
+            .ok()
+    });
+    state.write_queue.push(write, journal_seq);
+}
+
+/// Replay queued writes in order. Stops at the first failure, leaving the rest queued.
+/// Replayed writes are acknowledged in the journal so their segments can be compacted.
+pub fn replay_pending_writes(state: &AppState, conn: &mut mysql::PooledConn) -> Result<usize> {
+    let mut replayed = 0;
+    let mut last_seq = None;
+    let mut result = Ok(());
+    loop {
+        let queued = match state.write_queue.pop() {
+            Some(queued) => queued,
+            None => match state.journal.as_ref() {
+                // Writes that overflowed the queue follow from the journal
+                Some(journal) => match state.write_queue.refill(journal) {
+                    Ok(0) => break,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        result = Err(e).context("Failed to read queued writes back from the journal");
+                        break;
+                    },
+                },
+                None => break,
+            },
+        };
+        if let Err(e) = apply_write(conn, &queued.write, true) {
+            state.write_queue.push_front(queued);
+            state.db_breaker.record_failure();
+            result = Err(e).context("Failed to replay queued write");
+            break;
+        }
+        replayed += 1;
+        last_seq = queued.journal_seq.or(last_seq);
+    }
+
+    if replayed > 0 {
+        state.outbox.notify();
+    }
+    if let (Some(journal), Some(seq)) = (state.journal.as_ref(), last_seq) {
+        journal.acknowledge(seq)?;
+    }
+    result.map(|_| replayed)
+}
+
+/// Check whether MySQL is reachable again and flush the write queue
+fn probe_database(state: &AppState) -> Result<usize> {
+    if state.database_pool().is_none() {
+        match state.config.create_connection_pool() {
+            Ok(pool) => {
+                log::info!("MySQL connection pool established, hbd is ready");
+                state.set_database_pool(pool);
+                state.db_breaker.record_success();
+            },
+            Err(e) => {
+                state.db_breaker.record_failure();
+                return Err(e);
+            }
+        }
+    }
+
+    let mut conn = state.get_connection()?;
+    if let Err(e) = conn.query_drop("SELECT 1") {
+        state.db_breaker.record_failure();
+        return Err(e).context("MySQL probe query failed");
+    }
+    state.db_breaker.record_success();
+    replay_pending_writes(state, &mut conn)
+}
+
+/// Startup: connect to MySQL within connect_retry.deadline while the server already
+/// answers, /ready returning 503 until the pool exists, then hand over to the probe.
+/// Fails only when fail_on_deadline is set and MySQL stayed unreachable.
+pub async fn connect_at_startup(state: AppState) -> Result<()> {
+    let retry = &state.config.database.connect_retry;
+    if retry.deadline > 0 {
+        match state.config.create_connection_pool_with_retry().await {
+            Ok(pool) => {
+                // Runs the schema migrations, which block
+                let ready_state = state.clone();
+                tokio::task::spawn_blocking(move || {
+                    ready_state.set_database_pool(pool);
+                    ready_state.db_breaker.record_success();
+                }).await.context("Preparing the connection pool panicked")?;
+                log::info!("MySQL connection pool established, hbd is ready");
+            },
+            Err(e) if retry.fail_on_deadline => return Err(e).context("Failed to create database connection pool"),
+            Err(e) => log::warn!("MySQL unavailable at startup, staying not-ready and connecting in the background: {:#}", e),
+        }
+    }
+    tokio::spawn(run_database_probe(state));
+    Ok(())
+}
+
+/// Background task: while not ready or degraded (or with writes still queued),
+/// probe MySQL and replay the queue once it recovers.
+/// Until the first pool is created, attempts follow the connect_retry backoff.
+pub async fn run_database_probe(state: AppState) {
+    let interval = Duration::from_secs(state.config.database.circuit_breaker.probe_interval);
+    let mut connect_backoff = state.config.database.connect_retry.backoff();
+    loop {
+        let delay = if state.is_ready() {
+            connect_backoff.reset();
+            interval
+        } else {
+            connect_backoff.next_delay()
+        };
+        tokio::time::sleep(delay).await;
+        if !state.is_degraded() && state.write_queue.is_empty() {
+            continue;
+        }
+
+        let probe_state = state.clone();
+        match tokio::task::spawn_blocking(move || probe_database(&probe_state)).await {
+            Ok(Ok(0)) => {},
+            Ok(Ok(replayed)) => log::info!("Replayed {} queued writes to MySQL", replayed),
+            Ok(Err(e)) => log::debug!("MySQL still unavailable: {:#}", e),
+            Err(e) => log::error!("Database probe task panicked: {}", e),
+        }
+    }
+}
+
 /// Handle heartbeat with MySQL and cache integration
 /// This function can be called from handle_heartbeat in server.rs
 pub async fn handle_heartbeat_with_cache(
     state: AppState,
     params: HeartbeatQuery,
+    telemetry: Telemetry,
     heartbeat_cache: &HeartbeatCache<'_>,
     uninitialized: bool,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     let device_id = params.id;
     let mac_address = params.mac.clone();
     let ip_address = params.ip.clone();
-    let timestamp = params.timestamp;
-    let LP = params.long_poll;
-    let pip = get_pip();
-    // let authorized= get_authorized(&state, heartbeat_cache, &mac_address)?;
-    // let last_heartbeat_write =get_last_heartbeat_write(heartbeat_cache, &mac_address);
+    let pip = params.pip.clone().unwrap_or_else(get_pip);
     log::info!("Processing heartbeat for device ID: {}, MAC: {:?}, IP: {:?}", 
     device_id, mac_address, ip_address);
 
-    return Err(StatusCode::OK);
-    //if not authorized
-      // remove from hb_waiting heartbeat_cache, cache
-      // redirect to 
+    // Known devices are authorized from the cache, so they keep working while MySQL is down
+    let authorized = get_authorized(&state, heartbeat_cache, &mac_address)?;
+    let account_id = authorized.account_id;
+    if !authorized.authorized {
+        state.metrics.record(account_id, Counter::Rejected);
+        heartbeat_cache.remove_device(&mac_address);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let now = Utc::now();
+    let settings = account_id
+        .map(|account| state.accounts.settings(&state, account))
+        .unwrap_or_default();
+    if let (Some(account), Some(limit)) = (account_id, settings.rate_limit_per_minute)
+        && !state.accounts.allow_heartbeat(account, limit, now)
+    {
+        log::warn!("Account {} over its limit of {} heartbeats/minute, refusing {}", account, limit, mac_address);
+        state.metrics.record(account_id, Counter::RateLimited);
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+    state.metrics.record(account_id, Counter::Heartbeat);
+    let heartbeat_rate = state.load.record(now);
+
+    // Squelches set through the API apply on top of the flag from is_device_active
+    let squelch = state.squelches.find(&mac_address, authorized.zone_number, account_id, now);
+    if let Some(squelch) = &squelch {
+        log::debug!("{} squelched by squelch {} ({:?})", mac_address, squelch.id, squelch.reason);
+    }
+    let squelched = authorized.squelched || squelch.is_some();
+
+    if state.config.history.enabled {
+        state.history.record(&mac_address, now);
+    }
+    let cached = heartbeat_cache.get_device(&mac_address);
+    let mut last_heartbeat_write = get_last_heartbeat_write(heartbeat_cache, &mac_address);
+
+    // Telemetry is kept field by field. New firmware and reboots are written at once,
+    // other changed readings with the next heartbeat write.
+    let telemetry = telemetry.sanitize();
+    let telemetry_reported_at = if telemetry.is_empty() {
+        cached.as_ref().and_then(|device| device.telemetry_reported_at)
+    } else {
+        Some(now)
+    };
+    let previous_telemetry = cached.as_ref().map(|device| device.telemetry.clone()).unwrap_or_default();
+    let telemetry = previous_telemetry.clone().merge(telemetry);
+    let urgent_telemetry = telemetry.must_write_over(&previous_telemetry);
+    let mut telemetry_unwritten = cached.as_ref().is_some_and(|device| device.telemetry_unwritten)
+        || telemetry.changed_from(&previous_telemetry);
 
-    //if authorized but squelched 
-    // redirect to
-    
+    // Events go to the outbox in the same transaction as the device write below
+    let mut events = Vec::new();
+    if let Some(device) = &cached
+        && (device.local_ip_address != ip_address || device.global_ip_address != pip)
+    {
+        events.push(
+            DeviceEvent::device(EventKind::IpChanged, &mac_address, account_id, authorized.zone_number, Some(pip.clone()), now)
+                .with_details(json!({
+                    "local_ip_address": ip_address,
+                    "previous_local_ip_address": device.local_ip_address,
+                    "previous_global_ip_address": device.global_ip_address
+                }))
+        );
+    }
+    if uninitialized {
+        events.push(
+            DeviceEvent::device(EventKind::Provisioned, &mac_address, account_id, authorized.zone_number, Some(pip.clone()), now)
+                .with_details(json!({ "local_ip_address": ip_address }))
+        );
+    }
+    if let (Some(previous), Some(version)) = (&previous_telemetry.firmware_version, &telemetry.firmware_version)
+        && previous != version
+    {
+        events.push(
+            DeviceEvent::device(EventKind::FirmwareUpdated, &mac_address, account_id, authorized.zone_number, Some(pip.clone()), now)
+                .with_details(json!({
+                    "firmware_version": version,
+                    "previous_firmware_version": previous
+                }))
+        );
+    }
 
-    //if device exists in cache
-      // if eiher ip changes, write_to_db(set_device_last_heartbeat)
-      // if pip changes notify frontend
-      // if uninitialized write to db
+    // Tell the device when to report next; staleness checks use the same interval
+    let cache_config = state.config.app.cache.clone().unwrap_or_default();
+    let expected = intervals::expected_interval(authorized.heartbeat_interval, settings.heartbeat_interval, &cache_config);
+    let interval = intervals::effective_interval(expected, heartbeat_rate, &cache_config);
+    if interval > expected && cached.as_ref().is_none_or(|device| device.interval <= expected) {
+        log::debug!("Lengthening interval for {} to {}s at {:.0} heartbeats/s", mac_address, interval, heartbeat_rate);
+    }
 
-    // if uninitialized
-      // call sp: set_ready_device
+    // A longer interval is stored at once so MySQL does not see the device as stale early;
+    // a shorter one waits for the next write
+    let needs_write = uninitialized || !events.is_empty() || urgent_telemetry || match &cached {
+        None => true,
+        Some(device) => device.local_ip_address != ip_address
+            || device.global_ip_address != pip
+            || device.interval < interval
+            || last_heartbeat_write
+                .map(|written| (now - written).num_seconds() >= HEARTBEAT_WRITE_INTERVAL_SECS)
+                .unwrap_or(true),
+    };
 
-    // if now - cache entry_last_db_write > (max_hb_staleness - hb_staleness_period)  
-      // update db(sp : update_last_hb) if cache_entry_last_heartbeat > entry_last_db_write
+    if needs_write {
+        let changed_telemetry = telemetry_unwritten.then(|| telemetry.clone());
+        telemetry_unwritten = false;
+        let write = if uninitialized {
+            PendingWrite::ReadyDevice {
+                mac_address: mac_address.clone(),
+                local_ip_address: ip_address.clone(),
+                global_ip_address: pip.clone(),
+                received_at: now,
+                telemetry: changed_telemetry,
+                interval: Some(interval),
+            }
+        } else {
+            PendingWrite::Heartbeat {
+                mac_address: mac_address.clone(),
+                local_ip_address: ip_address.clone(),
+                global_ip_address: pip.clone(),
+                received_at: now,
+                telemetry: changed_telemetry,
+                interval: Some(interval),
+            }
+        };
+        if write_or_queue(&state, write, events) {
+            last_heartbeat_write = Some(now);
+        } else {
+            state.metrics.record(account_id, Counter::QueuedWrite);
+        }
+    }
+
+    if state.stream.has_subscribers() {
+        state.stream.heartbeat(LiveHeartbeat {
+            mac_address: mac_address.to_uppercase(),
+            account_id,
+            zone_number: authorized.zone_number,
+            local_ip_address: ip_address.clone(),
+            global_ip_address: pip.clone(),
+            squelched,
+            at: now,
+        });
+    }
+
+    let firmware = crate::firmware::check_in(
+        &state, &mac_address, authorized.zone_number, account_id, telemetry.firmware_version.as_deref(), now,
+    );
+
+    // Devices that rebooted together are sent to their own slots, spreading them over the interval
+    let slot_offset = intervals::slot_offset(&mac_address, interval);
+    state.slots.record(now, slot_offset, interval);
 
     // update cache either way
-    // let device_update = HeartbeatCacheInfo{
-    //     id: device_id,
-    //     mac_address: mac_address,
-    //     global_ip_address: pip,
-    //     local_ip_address: ip_address,
-    //     last_heartbeat: Utc::now(),
-    //     last_heartbeat_write: last_heartbeat_write,
-    // };
-    // heartbeat_cache.update_device(device_update);
-   
-    // get_status_code()
-      
+    heartbeat_cache.update_device(HeartbeatCacheInfo {
+        id: device_id,
+        mac_address,
+        global_ip_address: pip,
+        local_ip_address: ip_address,
+        last_heartbeat: now,
+        last_heartbeat_write,
+        account_id,
+        zone_number: authorized.zone_number,
+        squelched: authorized.squelched,
+        registry_interval: authorized.heartbeat_interval,
+        interval,
+        telemetry,
+        telemetry_reported_at,
+        telemetry_unwritten,
+    });
+
+    let mut response = serde_json::json!({
+        "status": "success",
+        "squelched": squelched,
+        "degraded": state.is_degraded(),
+        "account_id": account_id,
+        "interval": interval
+    });
+    if cache_config.heartbeat_slots {
+        response["slot_offset"] = slot_offset.into();
+        response["next_heartbeat_in"] = intervals::until_slot(now, slot_offset, interval).into();
+    }
+    if let Some(firmware) = firmware {
+        response["firmware"] = json!(firmware);
+    }
+    if squelched && let Some(redirect) = settings.redirect_url {
+        response["redirect"] = redirect.into();
+    }
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as ChronoDuration;
+
+    #[test]
+    fn test_replay_yields_to_newer_heartbeats() {
+        let received_at = Utc::now() - ChronoDuration::minutes(10);
+        assert!(replay_is_current(None, received_at));
+        assert!(replay_is_current(Some(received_at - ChronoDuration::minutes(1)), received_at));
+        assert!(replay_is_current(Some(received_at), received_at));
+        // Written directly after recovery: the replay must not restore its old addresses
+        assert!(!replay_is_current(Some(received_at + ChronoDuration::seconds(1)), received_at));
+    }
 }
@@ -0,0 +1,160 @@
+use axum::{
+    extract::{Query, Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+
+use serde::Deserialize;
+
+use crate::error::ApiError;
+use crate::server::AppState;
+
+/// Extract the bearer token from an Authorization header value
+fn bearer_token(value: &str) -> Option<&str> {
+    value.strip_prefix("Bearer ").map(str::trim).filter(|token| !token.is_empty())
+}
+
+/// Compare without short-circuiting on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Caller identity resolved from the bearer token, available to admin handlers as an extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Principal {
+    /// None for fleet-wide admin tokens, the account for account-scoped tokens
+    pub account_id: Option<i32>,
+}
+
+impl Principal {
+    /// True for fleet-wide admin tokens
+    pub fn is_global(&self) -> bool {
+        self.account_id.is_none()
+    }
+
+    /// True if the caller may see resources owned by `account_id`
+    pub fn can_access(&self, account_id: Option<i32>) -> bool {
+        self.is_global() || self.account_id == account_id
+    }
+
+    /// Refuse access to another account's resources
+    pub fn authorize(&self, account_id: Option<i32>) -> Result<(), ApiError> {
+        if self.can_access(account_id) { Ok(()) } else { Err(ApiError::forbidden()) }
+    }
+
+    /// Refuse fleet-wide operations to account-scoped callers
+    pub fn require_global(&self) -> Result<(), ApiError> {
+        if self.is_global() { Ok(()) } else { Err(ApiError::forbidden()) }
+    }
+}
+
+/// True if `token` matches one of the configured admin tokens
+pub fn is_admin_token(state: &AppState, token: &str) -> bool {
+    state.config.server.admin_tokens.iter()
+        .any(|admin| constant_time_eq(admin.as_bytes(), token.as_bytes()))
+}
+
+/// Resolve a bearer token to a fleet-wide or account-scoped principal: the tokens in
+/// config first, then the tokens issued to users
+pub fn resolve_principal(state: &AppState, token: &str) -> Result<Option<Principal>, ApiError> {
+    if is_admin_token(state, token) {
+        return Ok(Some(Principal { account_id: None }));
+    }
+    let scoped = state.config.server.account_tokens.iter()
+        .find(|scoped| constant_time_eq(scoped.token.as_bytes(), token.as_bytes()))
+        .map(|scoped| Principal { account_id: Some(scoped.account_id) });
+    match scoped {
+        Some(principal) => Ok(Some(principal)),
+        None => crate::users::find_principal(state, token),
+    }
+}
+
+#[derive(Deserialize)]
+struct UpgradeQuery {
+    access_token: Option<String>,
+}
+
+/// `access_token` query parameter of a WebSocket upgrade, URL-decoded; browsers
+/// cannot set an Authorization header when opening a WebSocket
+fn upgrade_token(request: &Request) -> Option<String> {
+    let is_upgrade = request.headers().get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    if !is_upgrade {
+        return None;
+    }
+    Query::<UpgradeQuery>::try_from_uri(request.uri()).ok()?.0
+        .access_token
+        .filter(|token| !token.is_empty())
+}
+
+/// Middleware guarding admin routes with `Authorization: Bearer <token>`
+/// (or `?access_token=` on WebSocket upgrades).
+/// Accepts admin tokens, account tokens and user tokens; the resolved `Principal` is
+/// added to the request extensions. User tokens cannot be checked while MySQL is down.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let token = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token)
+        .map(str::to_string)
+        .or_else(|| upgrade_token(&request));
+    let principal = match token {
+        Some(token) => resolve_principal(&state, &token)?,
+        None => None,
+    };
+    let Some(principal) = principal else {
+        log::warn!("Rejected unauthenticated admin request to {}", request.uri().path());
+        return Err(ApiError::unauthorized());
+    };
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc123"), Some("abc123"));
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("Basic abc123"), None);
+    }
+
+    #[test]
+    fn test_upgrade_token() {
+        let upgrade = |uri: &str| Request::builder().uri(uri).header(header::UPGRADE, "websocket")
+            .body(axum::body::Body::empty()).unwrap();
+        assert_eq!(upgrade_token(&upgrade("/ws?access_token=a%2Bb%3D%26c&x=1")).as_deref(), Some("a+b=&c"));
+        assert_eq!(upgrade_token(&upgrade("/ws?x=1&access_token=abc123")).as_deref(), Some("abc123"));
+        assert_eq!(upgrade_token(&upgrade("/ws?access_token=")), None);
+        assert_eq!(upgrade_token(&upgrade("/ws")), None);
+        // Only WebSocket upgrades may carry the token in the URL
+        let plain = Request::builder().uri("/ws?access_token=abc123").body(axum::body::Body::empty()).unwrap();
+        assert_eq!(upgrade_token(&plain), None);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+
+    #[test]
+    fn test_principal_scoping() {
+        let admin = Principal { account_id: None };
+        let tenant = Principal { account_id: Some(7) };
+        assert!(admin.can_access(Some(7)) && admin.can_access(None));
+        assert!(tenant.can_access(Some(7)));
+        assert!(!tenant.can_access(Some(8)));
+        assert!(!tenant.can_access(None));
+        assert!(tenant.require_global().is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+/// Exponential backoff with a ceiling, used for reconnects and retries
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    max: Duration,
+    multiplier: f64,
+    current: Duration,
+}
+
+impl ExponentialBackoff {
+    pub fn new(initial: Duration, max: Duration, multiplier: f64) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+            multiplier: multiplier.max(1.0),
+            current: initial,
+        }
+    }
+
+    /// Delay to wait before the next attempt; grows on every call until `max`
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = self.current.mul_f64(self.multiplier).min(self.max);
+        delay
+    }
+
+    /// Start over from the initial delay after a success
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+
+    /// Delay before retry number `attempt` (1-based), for retries whose state lives elsewhere
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt.saturating_sub(1).min(64) as i32);
+        Duration::from_secs_f64((self.initial.as_secs_f64() * factor).min(self.max.as_secs_f64()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grows_until_max_and_resets() {
+        let mut backoff = ExponentialBackoff::new(Duration::from_millis(100), Duration::from_millis(350), 2.0);
+        assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(200));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(350));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(350));
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_delay_for_attempt() {
+        let backoff = ExponentialBackoff::new(Duration::from_secs(5), Duration::from_secs(3600), 2.0);
+        assert_eq!(backoff.delay_for_attempt(1), Duration::from_secs(5));
+        assert_eq!(backoff.delay_for_attempt(3), Duration::from_secs(20));
+        assert_eq!(backoff.delay_for_attempt(200), Duration::from_secs(3600));
+    }
+}
@@ -0,0 +1,240 @@
+use async_nats::connection::State as ConnectionState;
+use axum::{extract::State, response::Json, Extension};
+use prost::Message;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::auth::Principal;
+use crate::config::{BrokerConfig, PayloadFormat};
+use crate::error::ApiError;
+use crate::events::DeviceEvent;
+use crate::server::AppState;
+
+/// How often buffered events are retried while the broker is unreachable
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Protobuf encoding of a DeviceEvent, see proto/device_event.proto
+#[derive(Clone, PartialEq, Message)]
+pub struct EventMessage {
+    #[prost(string, tag = "1")]
+    pub kind: String,
+    #[prost(int32, optional, tag = "2")]
+    pub account_id: Option<i32>,
+    #[prost(string, optional, tag = "3")]
+    pub mac_address: Option<String>,
+    #[prost(int32, optional, tag = "4")]
+    pub zone_number: Option<i32>,
+    #[prost(string, optional, tag = "5")]
+    pub global_ip_address: Option<String>,
+    #[prost(string, repeated, tag = "6")]
+    pub devices: Vec<String>,
+    #[prost(string, tag = "7")]
+    pub details_json: String,
+    #[prost(int64, tag = "8")]
+    pub at_unix_millis: i64,
+}
+
+impl From<&DeviceEvent> for EventMessage {
+    fn from(event: &DeviceEvent) -> Self {
+        Self {
+            kind: event.kind.as_str().to_string(),
+            account_id: event.account_id,
+            mac_address: event.mac_address.clone(),
+            zone_number: event.zone_number,
+            global_ip_address: event.global_ip_address.clone(),
+            devices: event.devices.clone(),
+            details_json: if event.details.is_null() { String::new() } else { event.details.to_string() },
+            at_unix_millis: event.at.timestamp_millis(),
+        }
+    }
+}
+
+/// Subject for an event, e.g. hbd.events.42.offline
+pub fn subject_for(template: &str, event: &DeviceEvent) -> String {
+    let account = event.account_id.map(|account| account.to_string()).unwrap_or_else(|| "global".to_string());
+    template.replace("{account}", &account).replace("{event}", event.kind.as_str())
+}
+
+pub fn encode(format: PayloadFormat, event: &DeviceEvent) -> Vec<u8> {
+    match format {
+        PayloadFormat::Json => serde_json::to_vec(event).expect("events serialize to JSON"),
+        PayloadFormat::Protobuf => EventMessage::from(event).encode_to_vec(),
+    }
+}
+
+/// An encoded event waiting to be published
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokerMessage {
+    pub subject: String,
+    pub payload: Vec<u8>,
+}
+
+/// Broker publishing counters since hbd started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct BrokerStats {
+    pub connected: bool,
+    /// Events waiting in the buffer
+    pub buffered: usize,
+    pub published: u64,
+    /// Events lost because the buffer was full or the publisher fell behind the event bus
+    pub dropped: u64,
+    pub publish_errors: u64,
+}
+
+/// Bounded FIFO of events for the broker, holding them while it is unreachable
+#[derive(Debug)]
+pub struct BrokerBuffer {
+    messages: Mutex<VecDeque<BrokerMessage>>,
+    stats: Mutex<BrokerStats>,
+    capacity: usize,
+}
+
+impl BrokerBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            messages: Mutex::new(VecDeque::new()),
+            stats: Mutex::new(BrokerStats::default()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Buffer a message, dropping the oldest one when the buffer is full
+    pub fn push(&self, message: BrokerMessage) {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() >= self.capacity && let Some(dropped) = messages.pop_front() {
+            log::warn!("Broker buffer full ({}), dropping event for {}", self.capacity, dropped.subject);
+            self.record_dropped(1);
+        }
+        messages.push_back(message);
+    }
+
+    fn pop(&self) -> Option<BrokerMessage> {
+        self.messages.lock().unwrap().pop_front()
+    }
+
+    fn push_front(&self, message: BrokerMessage) {
+        self.messages.lock().unwrap().push_front(message);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.messages.lock().unwrap().is_empty()
+    }
+
+    fn record_dropped(&self, count: u64) {
+        self.stats.lock().unwrap().dropped += count;
+    }
+
+    pub fn stats(&self) -> BrokerStats {
+        let buffered = self.messages.lock().unwrap().len();
+        BrokerStats { buffered, ..*self.stats.lock().unwrap() }
+    }
+}
+
+/// Publish buffered messages in order, stopping at the first failure
+async fn publish_buffered(client: &async_nats::Client, buffer: &BrokerBuffer) {
+    while let Some(message) = buffer.pop() {
+        match client.publish(message.subject.clone(), message.payload.clone().into()).await {
+            Ok(()) => buffer.stats.lock().unwrap().published += 1,
+            Err(e) => {
+                log::warn!("Failed to publish event to {}: {}", message.subject, e);
+                buffer.stats.lock().unwrap().publish_errors += 1;
+                buffer.push_front(message);
+                return;
+            }
+        }
+    }
+}
+
+/// Background task: publish events from the event bus to NATS. Events are
+/// buffered while NATS is unreachable and sent in order once it is back.
+pub async fn run_broker_publisher(state: AppState, config: BrokerConfig) {
+    let client = match async_nats::ConnectOptions::new()
+        .name("hbd")
+        .connection_timeout(Duration::from_secs(config.connect_timeout))
+        .retry_on_initial_connect()
+        .connect(config.url.as_str())
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to set up NATS client for {}, events will not be published: {}", config.url, e);
+            return;
+        }
+    };
+
+    let mut events = state.events.subscribe();
+    loop {
+        tokio::select! {
+            received = events.recv() => match received {
+                Ok(event) => state.broker.push(BrokerMessage {
+                    subject: subject_for(&config.subject, &event),
+                    payload: encode(config.format, &event),
+                }),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    log::warn!("Broker publisher fell behind, {} events were not published", missed);
+                    state.broker.record_dropped(missed);
+                },
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = tokio::time::sleep(RETRY_INTERVAL), if !state.broker.is_empty() => {},
+        }
+
+        let connected = client.connection_state() == ConnectionState::Connected;
+        let was_connected = std::mem::replace(&mut state.broker.stats.lock().unwrap().connected, connected);
+        if connected != was_connected {
+            log::info!("NATS {} {}", config.url, if connected { "connected" } else { "unreachable, buffering events" });
+        }
+        if connected {
+            publish_buffered(&client, &state.broker).await;
+        }
+    }
+}
+
+/// Broker publishing counters (fleet-wide admin tokens only)
+pub async fn get_broker_metrics(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<BrokerStats>, ApiError> {
+    principal.require_global()?;
+    Ok(Json(state.broker.stats()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventKind;
+    use chrono::Utc;
+
+    fn event(account: Option<i32>) -> DeviceEvent {
+        DeviceEvent::device(EventKind::Offline, "AA:BB", account, Some(5), None, Utc::now())
+    }
+
+    #[test]
+    fn test_subject_and_payloads() {
+        let template = "hbd.events.{account}.{event}";
+        assert_eq!(subject_for(template, &event(Some(42))), "hbd.events.42.offline");
+        assert_eq!(subject_for(template, &event(None)), "hbd.events.global.offline");
+
+        let original = event(Some(42));
+        let decoded = EventMessage::decode(encode(PayloadFormat::Protobuf, &original).as_slice()).unwrap();
+        assert_eq!(decoded.kind, "offline");
+        assert_eq!(decoded.account_id, Some(42));
+        assert_eq!(decoded.at_unix_millis, original.at.timestamp_millis());
+        let json: DeviceEvent = serde_json::from_slice(&encode(PayloadFormat::Json, &original)).unwrap();
+        assert_eq!(json, original);
+    }
+
+    #[test]
+    fn test_buffer_drops_oldest_when_full() {
+        let buffer = BrokerBuffer::new(2);
+        for subject in ["a", "b", "c"] {
+            buffer.push(BrokerMessage { subject: subject.to_string(), payload: Vec::new() });
+        }
+        let stats = buffer.stats();
+        assert_eq!((stats.buffered, stats.dropped), (2, 1));
+        assert_eq!(buffer.pop().unwrap().subject, "b");
+    }
+}
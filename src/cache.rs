@@ -1,12 +1,42 @@
 use lockfreehashmap::LockFreeHashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use crossbeam_utils::atomic::AtomicCell;
 
+use crate::telemetry::Telemetry;
+
 /// Simple in-memory cache for heartbeat data
 #[derive(Debug, Clone)]
 pub struct HeartbeatCache<'a> {
     pub devices: Arc<LockFreeHashMap<'a, String, HeartbeatCacheInfo>>,
+    /// MAC addresses present in `devices`; the lock-free map cannot be iterated
+    keys: Arc<Mutex<HashSet<String>>>,
+    /// Recent online/offline transitions per device, keyed by upper-case MAC
+    flaps: Arc<Mutex<HashMap<String, FlapState>>>,
+}
+
+/// Online/offline transitions of one device within the flap window
+#[derive(Debug, Clone, Default)]
+struct FlapState {
+    transitions: VecDeque<DateTime<Utc>>,
+    flapping: bool,
+}
+
+impl FlapState {
+    fn prune(&mut self, cutoff: DateTime<Utc>) {
+        while self.transitions.front().is_some_and(|at| *at < cutoff) {
+            self.transitions.pop_front();
+        }
+    }
+}
+
+/// Whether recording or re-checking transitions changed a device's flapping state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlapChange {
+    Unchanged,
+    Started,
+    Stopped,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -17,39 +47,139 @@ pub struct HeartbeatCacheInfo {
     pub local_ip_address: String,
     pub last_heartbeat: DateTime<Utc>,
     pub last_heartbeat_write: Option<DateTime<Utc>>,
+    /// Owning account reported by is_device_active
+    pub account_id: Option<i32>,
+    /// Zone from the device registry, used to match zone squelches
+    pub zone_number: Option<i32>,
+    /// Squelch flag reported by is_device_active
+    pub squelched: bool,
+    /// Device or zone heartbeat interval from the device registry
+    pub registry_interval: Option<u32>,
+    /// Heartbeat interval last returned to the device, in seconds
+    pub interval: u64,
+    /// Latest reading of each telemetry field the device has sent
+    pub telemetry: Telemetry,
+    /// Last heartbeat that carried telemetry
+    pub telemetry_reported_at: Option<DateTime<Utc>>,
+    /// Readings changed since telemetry was last written, waiting for the next heartbeat write
+    pub telemetry_unwritten: bool,
 }
 
 impl<'a> HeartbeatCache<'a> {
     pub fn new() -> Self {
         Self {
             devices: Arc::new(LockFreeHashMap::new()),
+            keys: Arc::new(Mutex::new(HashSet::new())),
+            flaps: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    /// Get device from cache by MAC address
+    /// Get device from cache by MAC address, in any case
     pub fn get_device(&self, mac_address: &str) -> Option<HeartbeatCacheInfo> {
         let guard = lockfreehashmap::pin();
-        self.devices.get(mac_address, &guard).cloned()
+        self.devices.get(&mac_address.to_uppercase(), &guard).cloned()
     }
 
-    /// Update or insert device in cache using MAC address as key
+    /// Update or insert device in cache using the upper-cased MAC address as key
     pub fn update_device(&self, device: HeartbeatCacheInfo) {
         let guard = lockfreehashmap::pin();
-        self.devices.insert(device.mac_address.clone(), device, &guard);
+        let key = device.mac_address.to_uppercase();
+        self.keys.lock().unwrap().insert(key.clone());
+        self.devices.insert(key, device, &guard);
     }
 
-    /// Remove device from cache by MAC address
+    /// Remove device from cache by MAC address, in any case
     pub fn remove_device(&self, mac_address: &str) {
         let guard = lockfreehashmap::pin();
-        self.devices.remove(mac_address, &guard);
+        let key = mac_address.to_uppercase();
+        self.devices.remove(&key, &guard);
+        self.keys.lock().unwrap().remove(&key);
     }
 
     /// Get the number of devices in cache
     pub fn len(&self) -> usize {
-        // Note: LockFreeHashMap doesn't have a direct len() method
-        // This is a limitation of the current implementation
-        // We could maintain a separate atomic counter if needed
-        0 // Placeholder
+        self.keys.lock().unwrap().len()
+    }
+
+    /// Snapshot of the MAC addresses currently cached
+    pub fn mac_addresses(&self) -> Vec<String> {
+        self.keys.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Snapshot of every cached device
+    pub fn devices(&self) -> Vec<HeartbeatCacheInfo> {
+        self.mac_addresses().iter().filter_map(|mac| self.get_device(mac)).collect()
+    }
+
+    /// Record an online/offline transition. A device starts flapping once it has
+    /// `threshold` transitions within `window`; a threshold of 0 disables detection.
+    pub fn record_transition(&self, mac_address: &str, now: DateTime<Utc>, window: ChronoDuration, threshold: usize) -> FlapChange {
+        if threshold == 0 {
+            return FlapChange::Unchanged;
+        }
+        let mut flaps = self.flaps.lock().unwrap();
+        let state = flaps.entry(mac_address.to_uppercase()).or_default();
+        state.transitions.push_back(now);
+        state.prune(now - window);
+        if !state.flapping && state.transitions.len() >= threshold {
+            state.flapping = true;
+            return FlapChange::Started;
+        }
+        FlapChange::Unchanged
+    }
+
+    /// Forget transitions older than `window`. A flapping device stops flapping once
+    /// it is below half the threshold, so a device near the threshold does not toggle.
+    pub fn settle_flapping(&self, mac_address: &str, now: DateTime<Utc>, window: ChronoDuration, threshold: usize) -> FlapChange {
+        let mut flaps = self.flaps.lock().unwrap();
+        let key = mac_address.to_uppercase();
+        let Some(state) = flaps.get_mut(&key) else {
+            return FlapChange::Unchanged;
+        };
+        state.prune(now - window);
+        let stopped = state.flapping && state.transitions.len() < threshold.div_ceil(2);
+        if stopped {
+            state.flapping = false;
+        }
+        if !state.flapping && state.transitions.is_empty() {
+            flaps.remove(&key);
+        }
+        if stopped { FlapChange::Stopped } else { FlapChange::Unchanged }
+    }
+
+    /// True while the device is marked as flapping
+    pub fn is_flapping(&self, mac_address: &str) -> bool {
+        self.flaps.lock().unwrap().get(&mac_address.to_uppercase()).is_some_and(|state| state.flapping)
+    }
+
+    /// Upper-case MACs of every flapping device
+    pub fn flapping_devices(&self) -> Vec<String> {
+        self.flaps.lock().unwrap().iter()
+            .filter(|(_, state)| state.flapping)
+            .map(|(mac, _)| mac.clone())
+            .collect()
+    }
+
+    /// Transitions recorded within the current window
+    pub fn transition_count(&self, mac_address: &str) -> usize {
+        self.flaps.lock().unwrap().get(&mac_address.to_uppercase()).map_or(0, |state| state.transitions.len())
+    }
+
+    /// Drop transition history for devices no longer in the cache
+    pub fn retain_flaps(&self, macs: &HashSet<String>) {
+        self.flaps.lock().unwrap().retain(|mac, _| macs.contains(mac));
+    }
+
+    /// Remove devices whose last heartbeat is older than `cutoff`, returning how many were removed
+    pub fn remove_stale(&self, cutoff: DateTime<Utc>) -> usize {
+        let mut removed = 0;
+        for device in self.devices() {
+            if device.last_heartbeat < cutoff {
+                self.remove_device(&device.mac_address);
+                removed += 1;
+            }
+        }
+        removed
     }
 }
 
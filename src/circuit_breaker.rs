@@ -0,0 +1,170 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// State of the database circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Database is healthy, all calls go through
+    Closed,
+    /// Database is considered down, calls are short-circuited
+    Open,
+    /// A single probe call is allowed through to test recovery
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Circuit breaker guarding access to MySQL.
+/// While open, callers fall back to the cache and queue their writes.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    inner: Mutex<BreakerInner>,
+    failure_threshold: u32,
+    open_duration: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+            }),
+            failure_threshold: failure_threshold.max(1),
+            open_duration,
+        }
+    }
+
+    /// Create a breaker that starts open, e.g. when MySQL was unreachable at startup
+    pub fn new_open(failure_threshold: u32, open_duration: Duration) -> Self {
+        let breaker = Self::new(failure_threshold, open_duration);
+        breaker.trip();
+        breaker
+    }
+
+    /// Current breaker state
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Returns true when the database should be considered unavailable
+    pub fn is_open(&self) -> bool {
+        self.state() != BreakerState::Closed
+    }
+
+    /// Check whether a database call may proceed.
+    /// Once the open period has elapsed the first caller becomes the half-open probe.
+    /// A probe that reports neither success nor failure within another open period
+    /// is given up on and the next caller probes instead.
+    pub fn allow_request(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state == BreakerState::Closed {
+            return true;
+        }
+        let elapsed = inner.opened_at.map(|t| t.elapsed()).unwrap_or_default();
+        if elapsed >= self.open_duration {
+            inner.state = BreakerState::HalfOpen;
+            inner.opened_at = Some(Instant::now());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record a successful database call.
+    /// Returns true if this closed a previously open breaker.
+    pub fn record_success(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let recovered = inner.state != BreakerState::Closed;
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        if recovered {
+            log::info!("Database circuit breaker closed, MySQL is reachable again");
+        }
+        recovered
+    }
+
+    /// Record a failed database call, opening the breaker past the threshold
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let should_open = inner.state == BreakerState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold;
+        if should_open {
+            if inner.state == BreakerState::Closed {
+                log::warn!(
+                    "Database circuit breaker opened after {} consecutive failures, entering degraded mode",
+                    inner.consecutive_failures
+                );
+            }
+            inner.state = BreakerState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Force the breaker open
+    pub fn trip(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state = BreakerState::Open;
+        inner.opened_at = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn test_half_open_probe_and_recovery() {
+        let breaker = CircuitBreaker::new_open(1, Duration::from_millis(20));
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.allow_request());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        // Only one probe at a time
+        assert!(!breaker.allow_request());
+        assert!(breaker.record_success());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(!breaker.record_success());
+    }
+
+    #[test]
+    fn test_silent_probe_is_replaced() {
+        let breaker = CircuitBreaker::new_open(1, Duration::from_millis(20));
+        assert!(!breaker.allow_request());
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.allow_request());
+        assert!(!breaker.allow_request());
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.allow_request());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+    }
+
+    #[test]
+    fn test_failed_probe_reopens() {
+        let breaker = CircuitBreaker::new_open(5, Duration::from_millis(0));
+        assert!(breaker.allow_request());
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use mysql::prelude::*;
+use std::time::Duration;
+
+use crate::config::CacheConfig;
+use crate::history::Resolution;
+use crate::server::AppState;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Records last touched before this are purged
+pub fn cutoff(now: DateTime<Utc>, max_record_age_days: u32) -> DateTime<Utc> {
+    now - ChronoDuration::days(i64::from(max_record_age_days))
+}
+
+/// Run `delete_batch`, which deletes at most `batch_size` rows and returns how many
+/// it deleted, until a batch comes back short. Returns the total deleted.
+pub fn delete_in_batches(batch_size: usize, mut delete_batch: impl FnMut() -> Result<u64>) -> Result<u64> {
+    let batch_size = batch_size.max(1) as u64;
+    let mut removed = 0;
+    loop {
+        let affected = delete_batch()?;
+        removed += affected;
+        if affected < batch_size {
+            return Ok(removed);
+        }
+    }
+}
+
+/// Delete history rows older than `cutoff` in batches of `batch_size`,
+/// so a large purge never holds long locks on the history tables
+pub fn purge_history(conn: &mut mysql::PooledConn, cutoff: DateTime<Utc>, batch_size: usize) -> Result<u64> {
+    let cutoff = cutoff.format(DB_DATETIME_FORMAT).to_string();
+    let mut removed = 0;
+    for resolution in Resolution::ALL {
+        let statement = format!("DELETE FROM {} WHERE bucket_start < ? LIMIT {}", resolution.table(), batch_size.max(1));
+        removed += delete_in_batches(batch_size, || {
+            conn.exec_drop(&statement, (&cutoff,))
+                .with_context(|| format!("Failed to purge {}", resolution.table()))?;
+            Ok(conn.affected_rows())
+        })?;
+    }
+    Ok(removed)
+}
+
+/// Run one cleanup pass: purge old history, published outbox events and stale cache entries
+fn run_cleanup_once(state: &AppState, cache_config: &CacheConfig) -> Result<()> {
+    let cutoff = cutoff(Utc::now(), cache_config.max_record_age);
+
+    let stale = state.heart_beat_cache.remove_stale(cutoff);
+    log::info!("Cleanup removed {} stale cache entries (last heartbeat before {})", stale, cutoff.to_rfc3339());
+
+    let mut conn = state.get_connection()?;
+    let purged = purge_history(&mut conn, cutoff, cache_config.cleanup_batch_size)?;
+    log::info!("Cleanup removed {} heartbeat history records older than {} days", purged, cache_config.max_record_age);
+
+    let retention = state.config.outbox.retention;
+    let published = crate::outbox::purge_published(&mut conn, Utc::now() - ChronoDuration::hours(retention as i64), cache_config.cleanup_batch_size)?;
+    log::info!("Cleanup removed {} published outbox events older than {} hours", published, retention);
+    Ok(())
+}
+
+/// Background task: every `cleanup_interval` hours purge records older than `max_record_age` days
+pub async fn run_cleanup(state: AppState, cache_config: CacheConfig) {
+    let interval = Duration::from_secs(cache_config.cleanup_interval * 3600);
+    loop {
+        tokio::time::sleep(interval).await;
+        let cleanup_state = state.clone();
+        let config = cache_config.clone();
+        match tokio::task::spawn_blocking(move || run_cleanup_once(&cleanup_state, &config)).await {
+            Ok(Ok(())) => {},
+            Ok(Err(e)) => log::warn!("Cleanup failed, will retry next interval: {:#}", e),
+            Err(e) => log::error!("Cleanup task panicked: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cutoff_and_batches() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        assert_eq!(cutoff(now, 30), Utc.with_ymd_and_hms(2026, 1, 30, 12, 0, 0).unwrap());
+        assert_eq!(cutoff(now, 0), now);
+
+        // Full batches keep going, the first short one ends the purge
+        let mut batches = vec![100, 100, 37, 100].into_iter();
+        let mut calls = 0;
+        let removed = delete_in_batches(100, || { calls += 1; Ok(batches.next().unwrap()) }).unwrap();
+        assert_eq!((removed, calls), (237, 3));
+
+        let mut calls = 0;
+        assert_eq!(delete_in_batches(100, || { calls += 1; Ok(0) }).unwrap(), 0);
+        assert_eq!(calls, 1);
+
+        let mut attempts = 0;
+        let failed = delete_in_batches(100, || { attempts += 1; anyhow::bail!("lost connection") });
+        assert!(failed.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,529 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use mysql::prelude::*;
+use mysql::TxOpts;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::events::{DeviceEvent, EventKind};
+use crate::server::{AppState, DeviceInfo};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 1000;
+/// MySQL error code for duplicate unique keys
+const ER_DUP_ENTRY: u16 = 1062;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceStatus {
+    Active,
+    Deactivated,
+    Online,
+    Offline,
+    /// Currently marked as flapping by the outage monitor
+    Flapping,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceQuery {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub zone: Option<i32>,
+    pub camera: Option<i32>,
+    pub status: Option<DeviceStatus>,
+    /// Only devices of this account; account tokens are always limited to their own
+    pub account: Option<i32>,
+    /// Only devices whose last heartbeat is at or after this time ("YYYY-MM-DD HH:MM:SS")
+    pub last_seen_after: Option<String>,
+    /// Only devices whose last heartbeat is before this time ("YYYY-MM-DD HH:MM:SS")
+    pub last_seen_before: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDeviceRequest {
+    pub mac_address: String,
+    pub local_ip_address: Option<String>,
+    pub global_ip_address: Option<String>,
+    pub camera_number: Option<i32>,
+    pub zone_number: Option<i32>,
+    /// Defaults to the caller's account for account tokens
+    pub account_id: Option<i32>,
+    /// Expected heartbeat interval in seconds, overriding the zone's and account's
+    pub heartbeat_interval: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDeviceRequest {
+    pub local_ip_address: Option<String>,
+    pub global_ip_address: Option<String>,
+    pub camera_number: Option<i32>,
+    pub zone_number: Option<i32>,
+    /// Move the device to another account (admin tokens only)
+    pub account_id: Option<i32>,
+    pub heartbeat_interval: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceList {
+    pub devices: Vec<DeviceInfo>,
+    pub total: u64,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+/// Seconds before a device counts as offline, bound to history.offline_after.
+/// Same rule as intervals::stale_after for the interval last returned to the device;
+/// devices that have not reported since it was stored fall back to their own or their zone's.
+pub const STALE_AFTER: &str = "GREATEST(?, 2 * COALESCE(devices.last_interval, devices.heartbeat_interval,
+    (SELECT zones.heartbeat_interval FROM zones WHERE zones.zone_number = devices.zone_number), 0))";
+
+fn device_columns() -> String {
+    format!("id, UPPER(mac_address), local_ip_address, global_ip_address,
+    DATE_FORMAT(last_heartbeat, '%Y-%m-%d %H:%i:%s'), camera_number, zone_number,
+    DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s'), DATE_FORMAT(last_modified, '%Y-%m-%d %H:%i:%s'), account_id,
+    deactivated_at IS NULL, COALESCE(last_heartbeat >= NOW() - INTERVAL {} SECOND, 0), heartbeat_interval,
+    CAST(UNIX_TIMESTAMP(last_heartbeat) AS SIGNED), last_interval", STALE_AFTER)
+}
+
+/// Read a device from the columns of device_columns(), in order.
+/// There are more of them than mysql::from_row takes as a tuple.
+fn device_from_row(mut row: mysql::Row) -> DeviceInfo {
+    DeviceInfo {
+        id: row.take(0).unwrap_or_default(),
+        mac_address: row.take(1).unwrap_or_default(),
+        local_ip_address: row.take(2).flatten(),
+        global_ip_address: row.take(3).flatten(),
+        last_heartbeat: row.take(4).flatten(),
+        camera_number: row.take(5).flatten(),
+        zone_number: row.take(6).flatten(),
+        created_at: row.take(7).flatten(),
+        last_modified: row.take(8).flatten(),
+        account_id: row.take(9).flatten(),
+        active: row.take(10).unwrap_or_default(),
+        online: row.take(11).unwrap_or_default(),
+        heartbeat_interval: row.take(12).flatten(),
+        interval: row.take::<Option<u32>, _>(14).flatten().map(u64::from),
+        flapping: false,
+        last_heartbeat_at: row.take::<Option<i64>, _>(13).flatten()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+    }
+}
+
+/// Fill in what the heartbeat cache knows: the flapping state and, for devices that
+/// have reported since startup, the interval they were given and whether they kept to it
+fn with_cached_status(state: &AppState, mut device: DeviceInfo) -> DeviceInfo {
+    device.flapping = state.heart_beat_cache.is_flapping(&device.mac_address);
+    if let Some(cached) = state.heart_beat_cache.get_device(&device.mac_address) {
+        let stale_after = crate::intervals::stale_after(Some(cached.interval), state.config.history.offline_after);
+        device.interval = Some(cached.interval);
+        device.online = (Utc::now() - cached.last_heartbeat).num_seconds() < stale_after as i64;
+    }
+    device
+}
+
+/// Drop a device from the heartbeat cache so its next heartbeat re-reads MySQL
+pub fn invalidate_cache(state: &AppState, mac: &str) {
+    state.heart_beat_cache.remove_device(mac);
+}
+
+/// Fetch a single device by MAC address
+pub fn find_device(conn: &mut mysql::PooledConn, mac: &str, offline_after: u64) -> mysql::Result<Option<DeviceInfo>> {
+    let row: Option<mysql::Row> = conn.exec_first(
+        format!("SELECT {} FROM devices WHERE mac_address = UPPER(?)", device_columns()),
+        (offline_after, mac),
+    )?;
+    Ok(row.map(device_from_row))
+}
+
+/// Fetch every device matching `where_clause`, whose placeholders are bound from `values`
+pub fn query_devices(
+    conn: &mut mysql::PooledConn,
+    where_clause: &str,
+    values: Vec<mysql::Value>,
+    offline_after: u64,
+) -> mysql::Result<Vec<DeviceInfo>> {
+    let mut params: Vec<mysql::Value> = vec![offline_after.into()];
+    params.extend(values);
+    let rows: Vec<mysql::Row> = conn.exec(
+        format!("SELECT {} FROM devices {} ORDER BY id", device_columns(), where_clause),
+        params,
+    )?;
+    Ok(rows.into_iter().map(device_from_row).collect())
+}
+
+/// MAC of another device already using this camera number in the zone.
+/// Inside a transaction the matching index range stays locked until commit,
+/// so a concurrent registration cannot take the same camera number meanwhile.
+pub fn camera_conflict<Q: Queryable>(
+    conn: &mut Q,
+    zone_number: i32,
+    camera_number: i32,
+    mac: &str,
+) -> mysql::Result<Option<String>> {
+    conn.exec_first(
+        "SELECT UPPER(mac_address) FROM devices
+         WHERE zone_number = ? AND camera_number = ? AND mac_address <> UPPER(?) AND deactivated_at IS NULL
+         FOR UPDATE",
+        (zone_number, camera_number, mac),
+    )
+}
+
+/// The zone and camera a device ends up with, when both are set and so must be unique
+fn camera_slot(zone_number: Option<i32>, camera_number: Option<i32>) -> Option<(i32, i32)> {
+    zone_number.zip(camera_number)
+}
+
+/// Refuse a zone/camera assignment that duplicates another active device's camera number
+fn check_camera_conflict<Q: Queryable>(
+    conn: &mut Q,
+    zone_number: Option<i32>,
+    camera_number: Option<i32>,
+    mac: &str,
+) -> Result<(), ApiError> {
+    let Some((zone, camera)) = camera_slot(zone_number, camera_number) else {
+        return Ok(());
+    };
+    match camera_conflict(conn, zone, camera, mac).map_err(|e| ApiError::database("Failed to check camera numbers", e))? {
+        Some(existing) => {
+            log::warn!("Refusing camera {} in zone {} for {}: already used by {}", camera, zone, mac, existing);
+            Err(ApiError::new(StatusCode::CONFLICT, "conflict",
+                format!("Camera {} in zone {} is already used by {}", camera, zone, existing)))
+        },
+        None => Ok(()),
+    }
+}
+
+/// Fetch a device the caller may see; other accounts' devices are reported as missing
+fn find_visible_device(
+    conn: &mut mysql::PooledConn,
+    principal: &Principal,
+    mac: &str,
+    offline_after: u64,
+) -> Result<DeviceInfo, ApiError> {
+    find_device(conn, mac, offline_after)
+        .map_err(|e| ApiError::database("Failed to get device", e))?
+        .filter(|device| principal.can_access(device.account_id))
+        .ok_or_else(|| ApiError::not_found(format!("Device {} not found", mac)))
+}
+
+/// List devices with pagination and filters
+pub async fn list_devices(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<DeviceQuery>,
+) -> Result<Json<DeviceList>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = params.offset.unwrap_or(0);
+    let offline_after = state.config.history.offline_after;
+
+    let mut filters: Vec<&str> = Vec::new();
+    let flapping_filter: String;
+    let online_filter: String;
+    let mut values: Vec<mysql::Value> = Vec::new();
+    if let Some(zone) = params.zone {
+        filters.push("zone_number = ?");
+        values.push(zone.into());
+    }
+    if let Some(camera) = params.camera {
+        filters.push("camera_number = ?");
+        values.push(camera.into());
+    }
+    match params.status {
+        Some(DeviceStatus::Active) => filters.push("deactivated_at IS NULL"),
+        Some(DeviceStatus::Deactivated) => filters.push("deactivated_at IS NOT NULL"),
+        Some(DeviceStatus::Online) => {
+            online_filter = format!("last_heartbeat >= NOW() - INTERVAL {} SECOND", STALE_AFTER);
+            filters.push(&online_filter);
+            values.push(offline_after.into());
+        },
+        Some(DeviceStatus::Offline) => {
+            online_filter = format!("(last_heartbeat IS NULL OR last_heartbeat < NOW() - INTERVAL {} SECOND)", STALE_AFTER);
+            filters.push(&online_filter);
+            values.push(offline_after.into());
+        },
+        Some(DeviceStatus::Flapping) => {
+            let flapping = state.heart_beat_cache.flapping_devices();
+            flapping_filter = if flapping.is_empty() {
+                "FALSE".to_string()
+            } else {
+                format!("mac_address IN ({})", vec!["?"; flapping.len()].join(", "))
+            };
+            filters.push(&flapping_filter);
+            values.extend(flapping.into_iter().map(mysql::Value::from));
+        },
+        None => {},
+    }
+    if params.account.is_some() {
+        principal.authorize(params.account)?;
+    }
+    if let Some(account) = principal.account_id.or(params.account) {
+        filters.push("account_id = ?");
+        values.push(account.into());
+    }
+    if let Some(after) = params.last_seen_after {
+        filters.push("last_heartbeat >= ?");
+        values.push(after.into());
+    }
+    if let Some(before) = params.last_seen_before {
+        filters.push("last_heartbeat < ?");
+        values.push(before.into());
+    }
+    let where_clause = if filters.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", filters.join(" AND "))
+    };
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let total: Option<u64> = conn.exec_first(format!("SELECT COUNT(*) FROM devices {}", where_clause), values.clone())
+        .map_err(|e| ApiError::database("Failed to count devices", e))?;
+
+    let mut page_values: Vec<mysql::Value> = vec![offline_after.into()];
+    page_values.extend(values);
+    page_values.push(limit.into());
+    page_values.push(offset.into());
+    let rows: Vec<mysql::Row> = conn.exec(
+        format!("SELECT {} FROM devices {} ORDER BY id LIMIT ? OFFSET ?", device_columns(), where_clause),
+        page_values,
+    ).map_err(|e| ApiError::database("Failed to list devices", e))?;
+
+    Ok(Json(DeviceList {
+        devices: rows.into_iter().map(|row| with_cached_status(&state, device_from_row(row))).collect(),
+        total: total.unwrap_or(0),
+        limit,
+        offset,
+    }))
+}
+
+/// Get a device by MAC address
+pub async fn get_device(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(mac): Path<String>,
+) -> Result<Json<DeviceInfo>, ApiError> {
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_visible_device(&mut conn, &principal, &mac, state.config.history.offline_after)
+        .map(|device| Json(with_cached_status(&state, device)))
+}
+
+/// Check a registration before touching the database
+fn validate_create(payload: &CreateDeviceRequest) -> Result<(), ApiError> {
+    if payload.mac_address.trim().is_empty() {
+        return Err(ApiError::bad_request("mac_address is required"));
+    }
+    if payload.heartbeat_interval == Some(0) {
+        return Err(ApiError::bad_request("heartbeat_interval must be at least 1 second"));
+    }
+    Ok(())
+}
+
+/// Check an update before touching the database; only admin tokens move devices between accounts
+fn validate_update(principal: &Principal, payload: &UpdateDeviceRequest) -> Result<(), ApiError> {
+    if payload.account_id.is_some() {
+        principal.require_global()?;
+    }
+    if payload.heartbeat_interval == Some(0) {
+        return Err(ApiError::bad_request("heartbeat_interval must be at least 1 second"));
+    }
+    Ok(())
+}
+
+/// A device that is already registered is a conflict, not a server error
+fn write_error(context: &str, e: mysql::Error) -> ApiError {
+    match e {
+        mysql::Error::MySqlError(ref err) if err.code == ER_DUP_ENTRY => {
+            ApiError::new(StatusCode::CONFLICT, "conflict", "Device is already registered")
+        },
+        e => ApiError::database(context, e),
+    }
+}
+
+/// Register a new device
+pub async fn create_device(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<CreateDeviceRequest>,
+) -> Result<(StatusCode, Json<DeviceInfo>), ApiError> {
+    validate_create(&payload)?;
+    let account_id = payload.account_id.or(principal.account_id);
+    principal.authorize(account_id)?;
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    // The camera check, the insert and its event commit together
+    let mut tx = conn.start_transaction(TxOpts::default())
+        .map_err(|e| ApiError::database("Failed to start transaction", e))?;
+    check_camera_conflict(&mut tx, payload.zone_number, payload.camera_number, &payload.mac_address)?;
+    tx.exec_drop(
+        "INSERT INTO devices (mac_address, local_ip_address, global_ip_address, camera_number, zone_number, account_id,
+            heartbeat_interval)
+         VALUES (UPPER(?), ?, ?, ?, ?, ?, ?)",
+        (&payload.mac_address, &payload.local_ip_address, &payload.global_ip_address,
+         payload.camera_number, payload.zone_number, account_id, payload.heartbeat_interval),
+    ).map_err(|e| write_error("Failed to create device", e))?;
+    crate::outbox::insert(&mut tx, &DeviceEvent::device(
+        EventKind::Provisioned,
+        &payload.mac_address.to_uppercase(),
+        account_id,
+        payload.zone_number,
+        payload.global_ip_address.clone(),
+        Utc::now(),
+    )).map_err(|e| ApiError::database("Failed to record device event", e))?;
+    tx.commit().map_err(|e| ApiError::database("Failed to create device", e))?;
+    state.outbox.notify();
+
+    invalidate_cache(&state, &payload.mac_address);
+    log::info!("Device {} registered to account {:?}", payload.mac_address, account_id);
+    find_device(&mut conn, &payload.mac_address, state.config.history.offline_after)
+        .map_err(|e| ApiError::database("Failed to read created device", e))?
+        .map(|device| (StatusCode::CREATED, Json(device)))
+        .ok_or_else(|| ApiError::not_found(format!("Device {} not found after registration", payload.mac_address)))
+}
+
+/// Update a device's addresses or topology
+pub async fn update_device(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(mac): Path<String>,
+    Json(payload): Json<UpdateDeviceRequest>,
+) -> Result<Json<DeviceInfo>, ApiError> {
+    validate_update(&principal, &payload)?;
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    let existing = find_visible_device(&mut conn, &principal, &mac, 0)?;
+    let mut tx = conn.start_transaction(TxOpts::default())
+        .map_err(|e| ApiError::database("Failed to start transaction", e))?;
+    check_camera_conflict(
+        &mut tx,
+        payload.zone_number.or(existing.zone_number),
+        payload.camera_number.or(existing.camera_number),
+        &mac,
+    )?;
+    tx.exec_drop(
+        "UPDATE devices SET
+            local_ip_address = COALESCE(?, local_ip_address),
+            global_ip_address = COALESCE(?, global_ip_address),
+            camera_number = COALESCE(?, camera_number),
+            zone_number = COALESCE(?, zone_number),
+            account_id = COALESCE(?, account_id),
+            heartbeat_interval = COALESCE(?, heartbeat_interval)
+         WHERE mac_address = UPPER(?)",
+        (&payload.local_ip_address, &payload.global_ip_address, payload.camera_number, payload.zone_number,
+         payload.account_id, payload.heartbeat_interval, &mac),
+    ).map_err(|e| ApiError::database("Failed to update device", e))?;
+    tx.commit().map_err(|e| ApiError::database("Failed to update device", e))?;
+
+    invalidate_cache(&state, &mac);
+    find_device(&mut conn, &mac, state.config.history.offline_after)
+        .map_err(|e| ApiError::database("Failed to read updated device", e))?
+        .map(|device| Json(with_cached_status(&state, device)))
+        .ok_or_else(|| ApiError::not_found(format!("Device {} not found", mac)))
+}
+
+/// Deactivate a device; its next heartbeat is re-authorized against MySQL
+pub async fn deactivate_device(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(mac): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_visible_device(&mut conn, &principal, &mac, 0)?;
+    conn.exec_drop(
+        "UPDATE devices SET deactivated_at = COALESCE(deactivated_at, UTC_TIMESTAMP()) WHERE mac_address = UPPER(?)",
+        (&mac,),
+    ).map_err(|e| ApiError::database("Failed to deactivate device", e))?;
+
+    invalidate_cache(&state, &mac);
+    log::info!("Device {} deactivated", mac);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Registry fields the heartbeat path needs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegistryStatus {
+    pub deactivated: bool,
+    pub zone_number: Option<i32>,
+    /// The device's own heartbeat interval, or its zone's
+    pub heartbeat_interval: Option<u32>,
+}
+
+pub fn registry_status(conn: &mut mysql::PooledConn, mac: &str) -> mysql::Result<RegistryStatus> {
+    let status: Option<(bool, Option<i32>, Option<u32>)> = conn.exec_first(
+        "SELECT devices.deactivated_at IS NOT NULL, devices.zone_number,
+                COALESCE(devices.heartbeat_interval, zones.heartbeat_interval)
+         FROM devices LEFT JOIN zones ON zones.zone_number = devices.zone_number
+         WHERE devices.mac_address = UPPER(?)",
+        (mac,),
+    )?;
+    Ok(status
+        .map(|(deactivated, zone_number, heartbeat_interval)| RegistryStatus { deactivated, zone_number, heartbeat_interval })
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(mac: &str) -> CreateDeviceRequest {
+        CreateDeviceRequest {
+            mac_address: mac.to_string(),
+            local_ip_address: None,
+            global_ip_address: None,
+            camera_number: Some(2),
+            zone_number: Some(5),
+            account_id: None,
+            heartbeat_interval: None,
+        }
+    }
+
+    fn update() -> UpdateDeviceRequest {
+        UpdateDeviceRequest {
+            local_ip_address: None,
+            global_ip_address: None,
+            camera_number: None,
+            zone_number: None,
+            account_id: None,
+            heartbeat_interval: None,
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(validate_create(&registration("AA:BB:CC:00:00:01")).is_ok());
+        assert_eq!(validate_create(&registration("  ")).unwrap_err().status, StatusCode::BAD_REQUEST);
+        let no_interval = CreateDeviceRequest { heartbeat_interval: Some(0), ..registration("AA") };
+        assert_eq!(validate_create(&no_interval).unwrap_err().status, StatusCode::BAD_REQUEST);
+
+        let admin = Principal { account_id: None };
+        let tenant = Principal { account_id: Some(3) };
+        let move_account = UpdateDeviceRequest { account_id: Some(4), ..update() };
+        assert!(validate_update(&admin, &move_account).is_ok());
+        assert_eq!(validate_update(&tenant, &move_account).unwrap_err().status, StatusCode::FORBIDDEN);
+        let no_interval = UpdateDeviceRequest { heartbeat_interval: Some(0), ..update() };
+        assert_eq!(validate_update(&tenant, &no_interval).unwrap_err().status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_conflict_checks() {
+        // Only a device with both a zone and a camera number can clash
+        assert_eq!(camera_slot(Some(5), Some(2)), Some((5, 2)));
+        assert_eq!(camera_slot(Some(5), None), None);
+        assert_eq!(camera_slot(None, Some(2)), None);
+
+        let duplicate = mysql::Error::MySqlError(mysql::MySqlError {
+            state: "23000".to_string(),
+            message: "Duplicate entry".to_string(),
+            code: ER_DUP_ENTRY,
+        });
+        assert_eq!(write_error("Failed to create device", duplicate).status, StatusCode::CONFLICT);
+        let other = mysql::Error::MySqlError(mysql::MySqlError {
+            state: "42S02".to_string(),
+            message: "Table doesn't exist".to_string(),
+            code: 1146,
+        });
+        assert_eq!(write_error("Failed to create device", other).status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Kinds of device lifecycle events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Online,
+    Offline,
+    /// The device's local or public IP address changed
+    IpChanged,
+    /// A device was registered or reported itself ready
+    Provisioned,
+    /// A device, zone or account was squelched through the API
+    Squelched,
+    /// Most devices in a zone or behind one public IP went offline together
+    SiteOffline,
+    /// A site outage is over
+    SiteRestored,
+    /// A device crossed the flap threshold; its online/offline events are suppressed
+    FlappingStarted,
+    /// A flapping device settled down
+    FlappingStopped,
+    /// A device reported a different firmware version than before
+    FirmwareUpdated,
+    /// A firmware campaign was paused because too many devices went offline after being offered it
+    RolloutPaused,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Online => "online",
+            EventKind::Offline => "offline",
+            EventKind::IpChanged => "ip_changed",
+            EventKind::Provisioned => "provisioned",
+            EventKind::Squelched => "squelched",
+            EventKind::SiteOffline => "site_offline",
+            EventKind::SiteRestored => "site_restored",
+            EventKind::FlappingStarted => "flapping_started",
+            EventKind::FlappingStopped => "flapping_stopped",
+            EventKind::FirmwareUpdated => "firmware_updated",
+            EventKind::RolloutPaused => "rollout_paused",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "online" => Some(EventKind::Online),
+            "offline" => Some(EventKind::Offline),
+            "ip_changed" => Some(EventKind::IpChanged),
+            "provisioned" => Some(EventKind::Provisioned),
+            "squelched" => Some(EventKind::Squelched),
+            "site_offline" => Some(EventKind::SiteOffline),
+            "site_restored" => Some(EventKind::SiteRestored),
+            "flapping_started" => Some(EventKind::FlappingStarted),
+            "flapping_stopped" => Some(EventKind::FlappingStopped),
+            "firmware_updated" => Some(EventKind::FirmwareUpdated),
+            "rollout_paused" => Some(EventKind::RolloutPaused),
+            _ => None,
+        }
+    }
+}
+
+/// A device or site event. Site events name the affected devices in `devices`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceEvent {
+    pub kind: EventKind,
+    pub account_id: Option<i32>,
+    pub mac_address: Option<String>,
+    pub zone_number: Option<i32>,
+    pub global_ip_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>,
+    /// Kind-specific details, e.g. previous addresses for ip_changed
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub details: serde_json::Value,
+    pub at: DateTime<Utc>,
+}
+
+impl DeviceEvent {
+    pub fn device(
+        kind: EventKind,
+        mac_address: &str,
+        account_id: Option<i32>,
+        zone_number: Option<i32>,
+        global_ip_address: Option<String>,
+        at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            kind,
+            account_id,
+            mac_address: Some(mac_address.to_string()),
+            zone_number,
+            global_ip_address,
+            devices: Vec::new(),
+            details: serde_json::Value::Null,
+            at,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// Fan-out of events to every subscribed sink, fed in order by the outbox relay.
+/// Subscribers that fall behind lose the oldest events.
+pub struct EventBus {
+    sender: broadcast::Sender<DeviceEvent>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: DeviceEvent) {
+        log::info!(
+            "Event {} for account {:?}: device {:?} zone {:?} ip {:?} ({} devices)",
+            event.kind.as_str(), event.account_id, event.mac_address, event.zone_number,
+            event.global_ip_address, event.devices.len()
+        );
+        // No subscribers is fine, the event is still logged
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DeviceEvent> {
+        self.sender.subscribe()
+    }
+}
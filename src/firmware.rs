@@ -0,0 +1,716 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use mysql::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::events::{DeviceEvent, EventKind};
+use crate::server::AppState;
+use crate::write_queue::PendingWrite;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Longest firmware version stored (firmware_campaigns.firmware_version)
+const MAX_FIRMWARE_VERSION_LEN: usize = 64;
+
+const CAMPAIGN_COLUMNS: &str = "id, name, firmware_version, firmware_url, account_id, zone_numbers, percentage,
+    max_offline_percent, status, paused_reason, DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s')";
+
+/// Row shape produced by CAMPAIGN_COLUMNS
+type CampaignRow = (u64, String, String, String, Option<i32>, Option<String>, u8, u8, String, Option<String>,
+    Option<String>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignStatus {
+    /// Offering firmware to the devices it targets
+    Active,
+    /// Stopped offering, by hand or because too many devices went offline
+    Paused,
+    Completed,
+    Cancelled,
+}
+
+impl CampaignStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CampaignStatus::Active => "active",
+            CampaignStatus::Paused => "paused",
+            CampaignStatus::Completed => "completed",
+            CampaignStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Completed and cancelled campaigns are over and cannot be resumed
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, CampaignStatus::Completed | CampaignStatus::Cancelled)
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "active" => Some(CampaignStatus::Active),
+            "paused" => Some(CampaignStatus::Paused),
+            "completed" => Some(CampaignStatus::Completed),
+            "cancelled" => Some(CampaignStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// A staged firmware rollout to `percentage` percent of the devices in its zones
+/// (every zone when empty) of its account (every account when None)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Campaign {
+    pub id: u64,
+    pub name: String,
+    /// Version devices should end up on
+    pub firmware_version: String,
+    pub firmware_url: String,
+    pub account_id: Option<i32>,
+    pub zone_numbers: Vec<i32>,
+    pub percentage: u8,
+    /// Share of offered devices that may be offline before the campaign pauses itself
+    pub max_offline_percent: u8,
+    pub status: CampaignStatus,
+    pub paused_reason: Option<String>,
+    pub created_at: Option<String>,
+}
+
+impl Campaign {
+    /// True if the campaign covers a device with this MAC, zone and account
+    pub fn targets(&self, mac: &str, zone: Option<i32>, account: Option<i32>) -> bool {
+        self.account_id.is_none_or(|a| Some(a) == account)
+            && (self.zone_numbers.is_empty() || zone.is_some_and(|zone| self.zone_numbers.contains(&zone)))
+            && self.in_rollout(mac)
+    }
+
+    /// Each device has a fixed percentile per campaign, so raising the percentage
+    /// only adds devices to the rollout
+    pub fn in_rollout(&self, mac: &str) -> bool {
+        let percentile = crate::intervals::stable_hash(&format!("{}/{}", self.id, mac.to_uppercase())) % 100;
+        percentile < u64::from(self.percentage)
+    }
+}
+
+fn campaign_from_row(row: mysql::Row) -> Option<Campaign> {
+    let (id, name, firmware_version, firmware_url, account_id, zone_numbers, percentage, max_offline_percent, status,
+        paused_reason, created_at): CampaignRow = mysql::from_row(row);
+    Some(Campaign {
+        id,
+        name,
+        firmware_version,
+        firmware_url,
+        account_id,
+        zone_numbers: zone_numbers.as_deref().unwrap_or("")
+            .split(',')
+            .filter_map(|zone| zone.trim().parse().ok())
+            .collect(),
+        percentage,
+        max_offline_percent,
+        status: CampaignStatus::parse(&status)?,
+        paused_reason,
+        created_at,
+    })
+}
+
+/// Firmware returned in a heartbeat response
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FirmwareOffer {
+    pub campaign_id: u64,
+    pub version: String,
+    pub url: String,
+}
+
+/// A device that was offered firmware and has not reported it yet
+#[derive(Debug, Clone, PartialEq)]
+struct PendingUpgrade {
+    campaign_id: u64,
+    version: String,
+    /// Version the device ran when it was offered the firmware
+    previous_version: String,
+}
+
+/// In-memory copy of the active campaigns and the devices they are waiting on,
+/// reloaded periodically so campaigns changed through any hbd instance apply
+pub struct CampaignSet {
+    campaigns: RwLock<Vec<Campaign>>,
+    /// Keyed by upper-case MAC
+    pending: Mutex<HashMap<String, PendingUpgrade>>,
+    /// Campaign and upper-case MAC of devices that have reported the campaign's version.
+    /// They are never offered it again, so moving on to a newer build is not undone.
+    reached: Mutex<HashSet<(u64, String)>>,
+}
+
+impl CampaignSet {
+    pub fn new() -> Self {
+        Self {
+            campaigns: RwLock::new(Vec::new()),
+            pending: Mutex::new(HashMap::new()),
+            reached: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn reload(&self, conn: &mut mysql::PooledConn) -> mysql::Result<usize> {
+        let rows: Vec<mysql::Row> = conn.query(format!(
+            "SELECT {} FROM firmware_campaigns WHERE status = 'active' ORDER BY id",
+            CAMPAIGN_COLUMNS
+        ))?;
+        let campaigns: Vec<Campaign> = rows.into_iter().filter_map(campaign_from_row).collect();
+        // Paused campaigns still record devices that come back on their version
+        let pending: Vec<(u64, String, String, Option<String>)> = conn.query(
+            "SELECT d.campaign_id, d.mac_address, c.firmware_version, d.previous_version
+             FROM firmware_campaign_devices d JOIN firmware_campaigns c ON c.id = d.campaign_id
+             WHERE d.upgraded_at IS NULL AND c.status IN ('active', 'paused')
+             ORDER BY d.offered_at",
+        )?;
+        let reached: Vec<(u64, String)> = conn.query(
+            "SELECT d.campaign_id, d.mac_address
+             FROM firmware_campaign_devices d JOIN firmware_campaigns c ON c.id = d.campaign_id
+             WHERE d.upgraded_at IS NOT NULL AND c.status IN ('active', 'paused')",
+        )?;
+        let count = campaigns.len();
+        // Devices seen on a campaign's version without having been offered it are only
+        // known here, so they are kept for as long as the campaign is loaded
+        let loaded: HashSet<u64> = campaigns.iter().map(|campaign| campaign.id)
+            .chain(pending.iter().map(|(campaign_id, ..)| *campaign_id))
+            .chain(reached.iter().map(|(campaign_id, _)| *campaign_id))
+            .collect();
+        *self.campaigns.write().unwrap() = campaigns;
+        *self.pending.lock().unwrap() = pending.into_iter()
+            .map(|(campaign_id, mac, version, previous_version)| (
+                mac.to_uppercase(),
+                PendingUpgrade { campaign_id, version, previous_version: previous_version.unwrap_or_default() },
+            ))
+            .collect();
+        let mut known = self.reached.lock().unwrap();
+        known.retain(|(campaign_id, _)| loaded.contains(campaign_id));
+        known.extend(reached.into_iter().map(|(campaign_id, mac)| (campaign_id, mac.to_uppercase())));
+        Ok(count)
+    }
+
+    pub fn active(&self) -> Vec<Campaign> {
+        self.campaigns.read().unwrap().clone()
+    }
+
+    /// Firmware for a device running `version`, from the oldest active campaign targeting it.
+    /// Devices that have reported the campaign's version, or moved off the version they were
+    /// offered it on, are left alone: `version` may be newer than the campaign's.
+    pub fn offer_for(&self, mac: &str, zone: Option<i32>, account: Option<i32>, version: &str) -> Option<FirmwareOffer> {
+        let mac = mac.to_uppercase();
+        let pending = self.pending.lock().unwrap();
+        let reached = self.reached.lock().unwrap();
+        self.campaigns.read().unwrap().iter()
+            .find(|campaign| campaign.firmware_version != version
+                && !reached.contains(&(campaign.id, mac.clone()))
+                && pending.get(&mac)
+                    .is_none_or(|offered| offered.campaign_id != campaign.id || offered.previous_version == version)
+                && campaign.targets(&mac, zone, account))
+            .map(|campaign| FirmwareOffer {
+                campaign_id: campaign.id,
+                version: campaign.firmware_version.clone(),
+                url: campaign.firmware_url.clone(),
+            })
+    }
+
+    /// Remember that a device was offered firmware; true the first time for this campaign
+    fn track_offer(&self, mac: &str, offer: &FirmwareOffer, previous_version: &str) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.get(mac).is_some_and(|offered| offered.campaign_id == offer.campaign_id) {
+            return false;
+        }
+        pending.insert(mac.to_string(), PendingUpgrade {
+            campaign_id: offer.campaign_id,
+            version: offer.version.clone(),
+            previous_version: previous_version.to_string(),
+        });
+        true
+    }
+
+    /// Note a device reporting `version`, returning the campaign it was waiting on if
+    /// that campaign's version is the one it now runs
+    fn complete(&self, mac: &str, version: &str) -> Option<u64> {
+        {
+            let mut reached = self.reached.lock().unwrap();
+            for campaign in self.campaigns.read().unwrap().iter().filter(|campaign| campaign.firmware_version == version) {
+                reached.insert((campaign.id, mac.to_string()));
+            }
+        }
+        let mut pending = self.pending.lock().unwrap();
+        match pending.get(mac) {
+            Some(upgrade) if upgrade.version == version => {
+                let upgrade = pending.remove(mac)?;
+                self.reached.lock().unwrap().insert((upgrade.campaign_id, mac.to_string()));
+                Some(upgrade.campaign_id)
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Store a firmware offer, inside the caller's transaction when given one
+pub fn persist_offer<Q: Queryable>(
+    conn: &mut Q,
+    campaign_id: u64,
+    mac: &str,
+    previous_version: &str,
+    offered_at: DateTime<Utc>,
+) -> mysql::Result<()> {
+    conn.exec_drop(
+        "INSERT IGNORE INTO firmware_campaign_devices (campaign_id, mac_address, previous_version, offered_at)
+         VALUES (?, ?, ?, ?)",
+        (campaign_id, mac, previous_version, offered_at.format(DB_DATETIME_FORMAT).to_string()),
+    )
+}
+
+/// Store a device coming back on its campaign's version
+pub fn persist_upgrade<Q: Queryable>(conn: &mut Q, campaign_id: u64, mac: &str, upgraded_at: DateTime<Utc>) -> mysql::Result<()> {
+    let upgraded_at = upgraded_at.format(DB_DATETIME_FORMAT).to_string();
+    conn.exec_drop(
+        "INSERT INTO firmware_campaign_devices (campaign_id, mac_address, offered_at, upgraded_at)
+         VALUES (?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE upgraded_at = COALESCE(upgraded_at, VALUES(upgraded_at))",
+        (campaign_id, mac, &upgraded_at, &upgraded_at),
+    )
+}
+
+/// Match a heartbeat's firmware version against the campaigns: record a device coming
+/// back on its campaign's version, and return the firmware to offer it, if any.
+/// Devices that do not report a firmware version are never offered one.
+pub fn check_in(
+    state: &AppState,
+    mac: &str,
+    zone: Option<i32>,
+    account: Option<i32>,
+    version: Option<&str>,
+    now: DateTime<Utc>,
+) -> Option<FirmwareOffer> {
+    let version = version?;
+    let mac = mac.to_uppercase();
+    // Both writes are journaled and replayed like heartbeats when MySQL is unavailable
+    if let Some(campaign_id) = state.firmware.complete(&mac, version) {
+        log::info!("{} upgraded to {} by firmware campaign {}", mac, version, campaign_id);
+        let write = PendingWrite::FirmwareUpgrade { campaign_id, mac_address: mac.clone(), upgraded_at: now };
+        crate::app_with_mysql_and_cache::write_or_queue(state, write, Vec::new());
+    }
+
+    let offer = state.firmware.offer_for(&mac, zone, account, version)?;
+    if state.firmware.track_offer(&mac, &offer, version) {
+        log::info!("Offering firmware {} to {} (campaign {})", offer.version, mac, offer.campaign_id);
+        let write = PendingWrite::FirmwareOffer {
+            campaign_id: offer.campaign_id,
+            mac_address: mac.clone(),
+            previous_version: version.to_string(),
+            offered_at: now,
+        };
+        crate::app_with_mysql_and_cache::write_or_queue(state, write, Vec::new());
+    }
+    Some(offer)
+}
+
+/// How far a campaign has got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CampaignProgress {
+    /// Devices the firmware was offered to
+    pub offered: u64,
+    /// Offered devices that came back on the campaign's version
+    pub upgraded: u64,
+    /// Offered devices currently offline, whether or not they upgraded
+    pub offline: u64,
+}
+
+fn campaign_progress(conn: &mut mysql::PooledConn, campaign_id: u64, offline_after: u64) -> mysql::Result<CampaignProgress> {
+    let row: Option<(u64, u64, u64)> = conn.exec_first(
+        format!(
+            "SELECT COUNT(*),
+                CAST(COALESCE(SUM(d.upgraded_at IS NOT NULL), 0) AS UNSIGNED),
+                CAST(COALESCE(SUM(devices.last_heartbeat IS NULL
+                    OR devices.last_heartbeat < NOW() - INTERVAL {} SECOND), 0) AS UNSIGNED)
+             FROM firmware_campaign_devices d JOIN devices ON devices.mac_address = d.mac_address
+             WHERE d.campaign_id = ?",
+            crate::devices::STALE_AFTER
+        ),
+        (offline_after, campaign_id),
+    )?;
+    Ok(row.map(|(offered, upgraded, offline)| CampaignProgress { offered, upgraded, offline }).unwrap_or_default())
+}
+
+/// True once enough devices were offered the firmware and too many of them are offline
+pub fn should_pause(progress: &CampaignProgress, max_offline_percent: u8, min_offered: u64) -> bool {
+    progress.offered >= min_offered.max(1)
+        && progress.offline * 100 > progress.offered * u64::from(max_offline_percent)
+}
+
+/// Reload the campaigns and pause any whose offered devices are going offline
+fn check_campaigns(state: &AppState) -> anyhow::Result<usize> {
+    let config = &state.config.firmware;
+    let mut conn = state.get_connection()?;
+    state.firmware.reload(&mut conn)?;
+
+    let mut paused = 0;
+    for campaign in state.firmware.active() {
+        let progress = campaign_progress(&mut conn, campaign.id, state.config.history.offline_after)?;
+        if !should_pause(&progress, campaign.max_offline_percent, config.min_offered) {
+            continue;
+        }
+        let reason = format!("{} of {} devices offered {} are offline", progress.offline, progress.offered,
+            campaign.firmware_version);
+        conn.exec_drop(
+            "UPDATE firmware_campaigns SET status = 'paused', paused_reason = ? WHERE id = ? AND status = 'active'",
+            (&reason, campaign.id),
+        )?;
+        if conn.affected_rows() == 0 {
+            continue;
+        }
+        log::warn!("Paused firmware campaign {} '{}': {}", campaign.id, campaign.name, reason);
+        paused += 1;
+        crate::outbox::emit(state, DeviceEvent {
+            kind: EventKind::RolloutPaused,
+            account_id: campaign.account_id,
+            mac_address: None,
+            zone_number: None,
+            global_ip_address: None,
+            devices: Vec::new(),
+            details: serde_json::json!({
+                "campaign_id": campaign.id,
+                "name": campaign.name,
+                "firmware_version": campaign.firmware_version,
+                "offered": progress.offered,
+                "upgraded": progress.upgraded,
+                "offline": progress.offline
+            }),
+            at: Utc::now(),
+        });
+    }
+    if paused > 0 {
+        state.firmware.reload(&mut conn)?;
+    }
+    Ok(paused)
+}
+
+/// Background task: reload firmware campaigns and pause failing ones
+pub async fn run_campaign_monitor(state: AppState, interval: Duration) {
+    loop {
+        let check_state = state.clone();
+        match tokio::task::spawn_blocking(move || check_campaigns(&check_state)).await {
+            Ok(Ok(_)) => {},
+            Ok(Err(e)) => log::debug!("Firmware campaign check failed, keeping previous campaigns: {:#}", e),
+            Err(e) => log::error!("Firmware campaign task panicked: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InventoryQuery {
+    pub zone: Option<i32>,
+    /// Only devices of this account; account tokens are always limited to their own
+    pub account: Option<i32>,
+}
+
+/// Number of active devices on one firmware version
+#[derive(Debug, Serialize)]
+pub struct FirmwareVersionCount {
+    /// None for devices that never reported a version
+    pub firmware_version: Option<String>,
+    pub devices: u64,
+}
+
+/// Active devices per reported firmware version
+pub async fn get_inventory(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<InventoryQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    principal.authorize(params.account.or(principal.account_id))?;
+    let mut filters = vec!["devices.deactivated_at IS NULL"];
+    let mut values: Vec<mysql::Value> = Vec::new();
+    if let Some(account) = principal.account_id.or(params.account) {
+        filters.push("devices.account_id = ?");
+        values.push(account.into());
+    }
+    if let Some(zone) = params.zone {
+        filters.push("devices.zone_number = ?");
+        values.push(zone.into());
+    }
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let versions: Vec<FirmwareVersionCount> = conn.exec_map(
+        format!(
+            "SELECT t.firmware_version, COUNT(*) FROM devices
+             LEFT JOIN device_telemetry t ON t.mac_address = devices.mac_address
+             WHERE {} GROUP BY t.firmware_version ORDER BY COUNT(*) DESC, t.firmware_version",
+            filters.join(" AND ")
+        ),
+        values,
+        |(firmware_version, devices)| FirmwareVersionCount { firmware_version, devices },
+    ).map_err(|e| ApiError::database("Failed to read firmware inventory", e))?;
+    let total: u64 = versions.iter().map(|version| version.devices).sum();
+    Ok(Json(serde_json::json!({ "versions": versions, "total": total })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCampaignRequest {
+    pub name: String,
+    pub firmware_version: String,
+    pub firmware_url: String,
+    /// Defaults to the caller's account for account tokens; fleet-wide campaigns need an admin token
+    pub account_id: Option<i32>,
+    /// Only devices in these zones (default: every zone)
+    #[serde(default)]
+    pub zone_numbers: Vec<i32>,
+    /// Share of the targeted devices to offer the firmware to, 1-100
+    pub percentage: u8,
+    /// Defaults to firmware.max_offline_percent
+    pub max_offline_percent: Option<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCampaignRequest {
+    /// Widen or narrow the rollout
+    pub percentage: Option<u8>,
+    pub max_offline_percent: Option<u8>,
+    /// Pause, resume (active), complete or cancel
+    pub status: Option<CampaignStatus>,
+}
+
+/// A campaign with its progress
+#[derive(Debug, Serialize)]
+pub struct CampaignResponse {
+    #[serde(flatten)]
+    pub campaign: Campaign,
+    pub progress: CampaignProgress,
+}
+
+fn validate_percentages(percentage: Option<u8>, max_offline_percent: Option<u8>) -> Result<(), ApiError> {
+    if percentage.is_some_and(|percentage| percentage == 0 || percentage > 100) {
+        return Err(ApiError::bad_request("percentage must be between 1 and 100"));
+    }
+    if max_offline_percent.is_some_and(|percent| percent > 100) {
+        return Err(ApiError::bad_request("max_offline_percent must be between 0 and 100"));
+    }
+    Ok(())
+}
+
+fn find_campaign(state: &AppState, conn: &mut mysql::PooledConn, principal: &Principal, id: u64) -> Result<CampaignResponse, ApiError> {
+    let row: Option<mysql::Row> = conn.exec_first(
+        format!("SELECT {} FROM firmware_campaigns WHERE id = ?", CAMPAIGN_COLUMNS),
+        (id,),
+    ).map_err(|e| ApiError::database("Failed to get firmware campaign", e))?;
+    let campaign = row.and_then(campaign_from_row)
+        .filter(|campaign| principal.can_access(campaign.account_id))
+        .ok_or_else(|| ApiError::not_found(format!("Firmware campaign {} not found", id)))?;
+    let progress = campaign_progress(conn, id, state.config.history.offline_after)
+        .map_err(|e| ApiError::database("Failed to read campaign progress", e))?;
+    Ok(CampaignResponse { campaign, progress })
+}
+
+fn reload_campaigns(state: &AppState, conn: &mut mysql::PooledConn) {
+    if let Err(e) = state.firmware.reload(conn) {
+        log::warn!("Failed to reload firmware campaigns: {}", e);
+    }
+}
+
+/// Start a firmware campaign
+pub async fn create_campaign(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<CreateCampaignRequest>,
+) -> Result<(StatusCode, Json<CampaignResponse>), ApiError> {
+    let version = request.firmware_version.trim();
+    if request.name.trim().is_empty() || version.is_empty() || version.len() > MAX_FIRMWARE_VERSION_LEN {
+        return Err(ApiError::bad_request(format!(
+            "name and a firmware_version of at most {} characters are required", MAX_FIRMWARE_VERSION_LEN
+        )));
+    }
+    if !(request.firmware_url.starts_with("http://") || request.firmware_url.starts_with("https://")) {
+        return Err(ApiError::bad_request("firmware_url must be an http(s) URL"));
+    }
+    validate_percentages(Some(request.percentage), request.max_offline_percent)?;
+    let account_id = request.account_id.or(principal.account_id);
+    principal.authorize(account_id)?;
+
+    let zone_numbers: Vec<String> = request.zone_numbers.iter().map(i32::to_string).collect();
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    conn.exec_drop(
+        "INSERT INTO firmware_campaigns
+            (name, firmware_version, firmware_url, account_id, zone_numbers, percentage, max_offline_percent)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        (
+            request.name.trim(),
+            version,
+            &request.firmware_url,
+            account_id,
+            (!zone_numbers.is_empty()).then(|| zone_numbers.join(",")),
+            request.percentage,
+            request.max_offline_percent.unwrap_or(state.config.firmware.max_offline_percent),
+        ),
+    ).map_err(|e| ApiError::database("Failed to create firmware campaign", e))?;
+    let id = conn.last_insert_id();
+    reload_campaigns(&state, &mut conn);
+
+    log::info!("Firmware campaign {} '{}' started: {} to {}% of account {:?} zones {:?}",
+        id, request.name, version, request.percentage, account_id, request.zone_numbers);
+    let campaign = find_campaign(&state, &mut conn, &principal, id)?;
+    Ok((StatusCode::CREATED, Json(campaign)))
+}
+
+/// List firmware campaigns visible to the caller, newest first
+pub async fn list_campaigns(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let (where_clause, values): (&str, Vec<mysql::Value>) = match principal.account_id {
+        Some(account) => ("WHERE account_id = ?", vec![account.into()]),
+        None => ("", Vec::new()),
+    };
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let rows: Vec<mysql::Row> = conn.exec(
+        format!("SELECT {} FROM firmware_campaigns {} ORDER BY id DESC", CAMPAIGN_COLUMNS, where_clause),
+        values,
+    ).map_err(|e| ApiError::database("Failed to list firmware campaigns", e))?;
+    let campaigns: Vec<Campaign> = rows.into_iter().filter_map(campaign_from_row).collect();
+    Ok(Json(serde_json::json!({ "campaigns": campaigns })))
+}
+
+/// Get a firmware campaign with its progress
+pub async fn get_campaign(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u64>,
+) -> Result<Json<CampaignResponse>, ApiError> {
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_campaign(&state, &mut conn, &principal, id).map(Json)
+}
+
+/// Change a campaign's percentage, offline limit or status. Resuming clears the pause reason.
+/// A campaign that has completed or been cancelled keeps its status
+fn check_transition(current: CampaignStatus, requested: Option<CampaignStatus>) -> Result<(), ApiError> {
+    match requested {
+        Some(status) if current.is_terminal() && status != current => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "conflict",
+            format!("Firmware campaign is {} and can no longer become {}", current.as_str(), status.as_str()),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub async fn update_campaign(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u64>,
+    Json(request): Json<UpdateCampaignRequest>,
+) -> Result<Json<CampaignResponse>, ApiError> {
+    validate_percentages(request.percentage, request.max_offline_percent)?;
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    let current = find_campaign(&state, &mut conn, &principal, id)?;
+    check_transition(current.campaign.status, request.status)?;
+    // The status guard also holds against a campaign completing since it was read
+    conn.exec_drop(
+        "UPDATE firmware_campaigns SET
+            percentage = COALESCE(?, percentage),
+            max_offline_percent = COALESCE(?, max_offline_percent),
+            paused_reason = IF(? = 'active' AND status NOT IN ('completed', 'cancelled'), NULL, paused_reason),
+            status = IF(status IN ('completed', 'cancelled'), status, COALESCE(?, status))
+         WHERE id = ?",
+        (
+            request.percentage,
+            request.max_offline_percent,
+            request.status.map(|status| status.as_str()),
+            request.status.map(|status| status.as_str()),
+            id,
+        ),
+    ).map_err(|e| ApiError::database("Failed to update firmware campaign", e))?;
+    reload_campaigns(&state, &mut conn);
+    log::info!("Firmware campaign {} updated: percentage {:?}, max_offline_percent {:?}, status {:?}",
+        id, request.percentage, request.max_offline_percent, request.status);
+    find_campaign(&state, &mut conn, &principal, id).map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campaign(percentage: u8) -> Campaign {
+        Campaign {
+            id: 7,
+            name: "2.1 rollout".to_string(),
+            firmware_version: "2.1.0".to_string(),
+            firmware_url: "https://firmware.example.com/2.1.0.bin".to_string(),
+            account_id: Some(3),
+            zone_numbers: vec![5],
+            percentage,
+            max_offline_percent: 10,
+            status: CampaignStatus::Active,
+            paused_reason: None,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn test_rollout_targets_and_widens() {
+        let macs: Vec<String> = (0..1000).map(|n| format!("AA:BB:CC:DD:{:02X}:{:02X}", n / 256, n % 256)).collect();
+        let in_rollout = |campaign: &Campaign| -> Vec<&String> {
+            macs.iter().filter(|mac| campaign.targets(mac, Some(5), Some(3))).collect()
+        };
+        let ten = in_rollout(&campaign(10));
+        let fifty = in_rollout(&campaign(50));
+        assert!((60..=140).contains(&ten.len()));
+        assert!(ten.iter().all(|mac| fifty.contains(mac)));
+        assert_eq!(in_rollout(&campaign(100)).len(), 1000);
+
+        let everyone = campaign(100);
+        assert!(!everyone.targets("AA:BB", Some(6), Some(3)));
+        assert!(!everyone.targets("AA:BB", Some(5), Some(4)));
+        assert!(everyone.targets("aa:bb", Some(5), Some(3)));
+
+        let set = CampaignSet::new();
+        *set.campaigns.write().unwrap() = vec![everyone];
+        assert!(set.offer_for("AA:BB", Some(5), Some(3), "2.1.0").is_none());
+        let offer = set.offer_for("AA:BB", Some(5), Some(3), "2.0.4").unwrap();
+        assert!(set.track_offer("AA:BB", &offer, "2.0.4"));
+        assert!(!set.track_offer("AA:BB", &offer, "2.0.4"));
+        assert!(set.offer_for("AA:BB", Some(5), Some(3), "2.0.4").is_some());
+        assert!(set.offer_for("AA:BB", Some(5), Some(3), "2.2.0").is_none());
+        assert_eq!(set.complete("AA:BB", "2.0.4"), None);
+        assert_eq!(set.complete("AA:BB", "2.1.0"), Some(7));
+        assert!(set.offer_for("AA:BB", Some(5), Some(3), "2.2.0").is_none());
+
+        // A device seen on the campaign's version is not offered it once it moves on
+        assert_eq!(set.complete("CC:DD", "2.1.0"), None);
+        assert!(set.offer_for("CC:DD", Some(5), Some(3), "2.2.0").is_none());
+        assert!(set.offer_for("EE:FF", Some(5), Some(3), "2.0.4").is_some());
+    }
+
+    #[test]
+    fn test_pauses_when_offered_devices_go_offline() {
+        let progress = |offered, offline| CampaignProgress { offered, upgraded: 0, offline };
+        assert!(!should_pause(&progress(4, 4), 10, 5));
+        assert!(!should_pause(&progress(20, 2), 10, 5));
+        assert!(should_pause(&progress(20, 3), 10, 5));
+        assert!(should_pause(&progress(1, 1), 0, 0));
+    }
+
+    #[test]
+    fn test_terminal_campaigns_keep_their_status() {
+        use CampaignStatus::*;
+        assert!(check_transition(Active, Some(Paused)).is_ok());
+        assert!(check_transition(Paused, Some(Active)).is_ok());
+        assert!(check_transition(Active, Some(Cancelled)).is_ok());
+        for terminal in [Completed, Cancelled] {
+            assert!(check_transition(terminal, None).is_ok());
+            assert!(check_transition(terminal, Some(terminal)).is_ok());
+            for status in [Active, Paused, Completed, Cancelled].into_iter().filter(|status| *status != terminal) {
+                assert_eq!(check_transition(terminal, Some(status)).unwrap_err().status, StatusCode::CONFLICT);
+            }
+        }
+    }
+}
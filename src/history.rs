@@ -0,0 +1,305 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Duration as ChronoDuration, DurationRound, Utc};
+use mysql::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::server::AppState;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Granularity of a heartbeat history bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Minute,
+    Hour,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 2] = [Resolution::Minute, Resolution::Hour];
+
+    pub fn table(&self) -> &'static str {
+        match self {
+            Resolution::Minute => "heartbeat_history_minute",
+            Resolution::Hour => "heartbeat_history_hour",
+        }
+    }
+
+    pub fn duration(&self) -> ChronoDuration {
+        match self {
+            Resolution::Minute => ChronoDuration::minutes(1),
+            Resolution::Hour => ChronoDuration::hours(1),
+        }
+    }
+
+    /// Start of the bucket containing `at`
+    pub fn bucket_start(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        at.duration_trunc(self.duration()).unwrap_or(at)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Bucket {
+    count: u32,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+impl Bucket {
+    fn merge(&mut self, other: &Bucket) {
+        self.count = self.count.saturating_add(other.count);
+        self.first_seen = self.first_seen.min(other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+    }
+}
+
+type BucketKey = (Resolution, String, DateTime<Utc>);
+
+/// In-memory aggregator of heartbeat arrivals.
+/// The heartbeat path only bumps a counter; a background task flushes the
+/// buckets to MySQL so history costs no extra query per request.
+#[derive(Debug)]
+pub struct HeartbeatHistory {
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+    max_pending_buckets: usize,
+    dropped: AtomicU64,
+}
+
+impl HeartbeatHistory {
+    pub fn new(max_pending_buckets: usize) -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            max_pending_buckets,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a heartbeat arrival for a device
+    pub fn record(&self, mac_address: &str, at: DateTime<Utc>) {
+        let mac_address = mac_address.to_uppercase();
+        let arrival = Bucket { count: 1, first_seen: at, last_seen: at };
+        let mut buckets = self.buckets.lock().unwrap();
+        for resolution in Resolution::ALL {
+            let key = (resolution, mac_address.clone(), resolution.bucket_start(at));
+            if let Some(bucket) = buckets.get_mut(&key) {
+                bucket.merge(&arrival);
+            } else if buckets.len() < self.max_pending_buckets {
+                buckets.insert(key, arrival);
+            } else {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Number of buckets waiting to be flushed
+    pub fn pending(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+
+    fn take(&self) -> HashMap<BucketKey, Bucket> {
+        std::mem::take(&mut *self.buckets.lock().unwrap())
+    }
+
+    /// Put buckets back after a failed flush, merging with anything recorded since
+    fn restore(&self, pending: HashMap<BucketKey, Bucket>) {
+        let mut buckets = self.buckets.lock().unwrap();
+        for (key, bucket) in pending {
+            buckets.entry(key)
+                .and_modify(|existing| existing.merge(&bucket))
+                .or_insert(bucket);
+        }
+    }
+
+    /// Upsert all pending buckets into MySQL. On failure the buckets are kept
+    /// for the next flush, so history recorded during an outage is not lost.
+    pub fn flush(&self, state: &AppState) -> Result<usize> {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            log::warn!("Heartbeat history dropped {} arrivals, pending bucket limit ({}) reached",
+                dropped, self.max_pending_buckets);
+        }
+
+        let pending = self.take();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        let count = pending.len();
+        match state.get_connection().and_then(|mut conn| write_buckets(&mut conn, &pending)) {
+            Ok(()) => Ok(count),
+            Err(e) => {
+                self.restore(pending);
+                Err(e)
+            }
+        }
+    }
+}
+
+fn write_buckets(conn: &mut mysql::PooledConn, pending: &HashMap<BucketKey, Bucket>) -> Result<()> {
+    for resolution in Resolution::ALL {
+        let statement = format!(
+            "INSERT INTO {} (mac_address, bucket_start, heartbeat_count, first_seen, last_seen)
+             VALUES (?, ?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE
+                heartbeat_count = heartbeat_count + VALUES(heartbeat_count),
+                first_seen = LEAST(first_seen, VALUES(first_seen)),
+                last_seen = GREATEST(last_seen, VALUES(last_seen))",
+            resolution.table()
+        );
+        let rows = pending.iter()
+            .filter(|((res, _, _), _)| *res == resolution)
+            .map(|((_, mac, start), bucket)| (
+                mac.clone(),
+                start.format(DB_DATETIME_FORMAT).to_string(),
+                bucket.count,
+                bucket.first_seen.format(DB_DATETIME_FORMAT).to_string(),
+                bucket.last_seen.format(DB_DATETIME_FORMAT).to_string(),
+            ));
+        conn.exec_batch(statement, rows)
+            .with_context(|| format!("Failed to write {}", resolution.table()))?;
+    }
+    Ok(())
+}
+
+/// Background task: periodically flush heartbeat history to MySQL
+pub async fn run_history_flush(state: AppState, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let flush_state = state.clone();
+        match tokio::task::spawn_blocking(move || flush_state.history.flush(&flush_state)).await {
+            Ok(Ok(0)) => {},
+            Ok(Ok(count)) => log::debug!("Flushed {} heartbeat history buckets", count),
+            Ok(Err(e)) => log::warn!("Heartbeat history flush failed, will retry: {:#}", e),
+            Err(e) => log::error!("Heartbeat history flush task panicked: {}", e),
+        }
+    }
+}
+
+/// A stored history bucket
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryBucket {
+    pub bucket_start: String,
+    pub heartbeat_count: u32,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// Read history buckets for one device in [from, to)
+pub fn query_history(
+    conn: &mut mysql::PooledConn,
+    mac_address: &str,
+    resolution: Resolution,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> mysql::Result<Vec<HistoryBucket>> {
+    let statement = format!(
+        "SELECT DATE_FORMAT(bucket_start, '%Y-%m-%dT%H:%i:%sZ'), heartbeat_count,
+                DATE_FORMAT(first_seen, '%Y-%m-%dT%H:%i:%sZ'), DATE_FORMAT(last_seen, '%Y-%m-%dT%H:%i:%sZ')
+         FROM {}
+         WHERE mac_address = UPPER(?) AND bucket_start >= ? AND bucket_start < ?
+         ORDER BY bucket_start",
+        resolution.table()
+    );
+    conn.exec_map(
+        statement,
+        (mac_address, from.format(DB_DATETIME_FORMAT).to_string(), to.format(DB_DATETIME_FORMAT).to_string()),
+        |(bucket_start, heartbeat_count, first_seen, last_seen)| HistoryBucket {
+            bucket_start,
+            heartbeat_count,
+            first_seen,
+            last_seen,
+        },
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub resolution: Option<Resolution>,
+}
+
+/// Get heartbeat history for a device (defaults to the last 24 hours by minute)
+pub async fn get_device_history(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(mac): Path<String>,
+    Query(params): Query<HistoryQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let to = params.to.unwrap_or_else(Utc::now);
+    let from = params.from.unwrap_or(to - ChronoDuration::hours(24));
+    let resolution = params.resolution.unwrap_or(Resolution::Minute);
+    if from >= to {
+        return Err(ApiError::bad_request("from must be before to"));
+    }
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    if !principal.is_global() {
+        let device = crate::devices::find_device(&mut conn, &mac, 0)
+            .map_err(|e| ApiError::database("Failed to look up device", e))?;
+        if !device.is_some_and(|device| principal.can_access(device.account_id)) {
+            return Err(ApiError::not_found(format!("Device {} not found", mac)));
+        }
+    }
+    let buckets = query_history(&mut conn, &mac, resolution, from, to)
+        .map_err(|e| ApiError::database("Failed to query heartbeat history", e))?;
+
+    Ok(Json(serde_json::json!({
+        "mac_address": mac.to_uppercase(),
+        "resolution": resolution,
+        "from": from.to_rfc3339(),
+        "to": to.to_rfc3339(),
+        "buckets": buckets
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_records_into_minute_and_hour_buckets() {
+        let history = HeartbeatHistory::new(100);
+        let t1 = Utc.with_ymd_and_hms(2025, 1, 1, 10, 15, 5).unwrap();
+        let t2 = Utc.with_ymd_and_hms(2025, 1, 1, 10, 15, 50).unwrap();
+        let t3 = Utc.with_ymd_and_hms(2025, 1, 1, 10, 16, 0).unwrap();
+        history.record("aa:bb", t1);
+        history.record("AA:BB", t2);
+        history.record("aa:bb", t3);
+
+        let buckets = history.take();
+        assert_eq!(buckets.len(), 3);
+        let minute = buckets[&(Resolution::Minute, "AA:BB".to_string(), Resolution::Minute.bucket_start(t1))];
+        assert_eq!(minute.count, 2);
+        assert_eq!(minute.first_seen, t1);
+        assert_eq!(minute.last_seen, t2);
+        let hour = buckets[&(Resolution::Hour, "AA:BB".to_string(), Resolution::Hour.bucket_start(t1))];
+        assert_eq!(hour.count, 3);
+    }
+
+    #[test]
+    fn test_restore_merges_with_new_arrivals() {
+        let history = HeartbeatHistory::new(100);
+        let at = Utc.with_ymd_and_hms(2025, 1, 1, 10, 15, 5).unwrap();
+        history.record("AA", at);
+        let pending = history.take();
+        history.record("AA", at + ChronoDuration::seconds(10));
+        history.restore(pending);
+
+        let buckets = history.take();
+        let minute = buckets[&(Resolution::Minute, "AA".to_string(), Resolution::Minute.bucket_start(at))];
+        assert_eq!(minute.count, 2);
+        assert_eq!(minute.first_seen, at);
+    }
+}
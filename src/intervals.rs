@@ -0,0 +1,276 @@
+use axum::{
+    extract::State,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::sync::Mutex;
+
+use crate::auth::Principal;
+use crate::config::CacheConfig;
+use crate::error::ApiError;
+use crate::server::AppState;
+
+/// Intervals a device may miss before it counts as offline
+const MISSED_INTERVALS: u64 = 2;
+/// Phase buckets arrivals are counted in, each covering 1/SLOT_BUCKETS of an interval
+const SLOT_BUCKETS: usize = 60;
+/// Arrivals within this share of the interval from their slot count as on slot
+const SLOT_TOLERANCE: f64 = 0.05;
+/// Seconds over which arrival distribution is measured
+const SLOT_WINDOW_SECS: i64 = 3600;
+
+/// Heartbeats received per minute, used to lengthen intervals under load
+pub struct LoadMonitor {
+    window: Mutex<LoadWindow>,
+}
+
+#[derive(Default)]
+struct LoadWindow {
+    minute: i64,
+    count: u64,
+    /// Heartbeats per second over the last complete minute
+    rate: f64,
+}
+
+impl LoadMonitor {
+    pub fn new() -> Self {
+        Self { window: Mutex::new(LoadWindow::default()) }
+    }
+
+    /// Count a heartbeat and return the rate measured over the previous minute
+    pub fn record(&self, now: DateTime<Utc>) -> f64 {
+        let minute = now.timestamp().div_euclid(60);
+        let mut window = self.window.lock().unwrap();
+        if window.minute != minute {
+            // A minute without heartbeats in between means there was no load to speak of
+            window.rate = if minute == window.minute + 1 { window.count as f64 / 60.0 } else { 0.0 };
+            window.minute = minute;
+            window.count = 0;
+        }
+        window.count += 1;
+        window.rate
+    }
+}
+
+/// The interval a device is expected to keep: its own or its zone's, then its
+/// account's, then the configured default. Never beyond max_interval.
+pub fn expected_interval(registry: Option<u32>, account: Option<u32>, config: &CacheConfig) -> u64 {
+    registry.or(account)
+        .map(u64::from)
+        .unwrap_or(config.default_interval)
+        .clamp(1, config.max_interval.max(1))
+}
+
+/// The interval handed to the device. Above target_heartbeat_rate the expected
+/// interval is stretched in proportion to the load, up to max_interval; it is
+/// never shortened.
+pub fn effective_interval(expected: u64, rate: f64, config: &CacheConfig) -> u64 {
+    let target = config.target_heartbeat_rate as f64;
+    if !config.adaptive_intervals || target <= 0.0 || rate <= target {
+        return expected;
+    }
+    let stretched = (expected as f64 * rate / target).ceil() as u64;
+    stretched.clamp(expected, config.max_interval.max(expected))
+}
+
+/// Seconds without a heartbeat before a device counts as offline: history.offline_after,
+/// or longer for devices told to report less often
+pub fn stale_after(interval: Option<u64>, offline_after: u64) -> u64 {
+    interval.map_or(offline_after, |interval| offline_after.max(interval * MISSED_INTERVALS))
+}
+
+/// FNV-1a hash, which unlike std's hasher is stable across builds, so every hbd
+/// instance derives the same value from a MAC
+pub fn stable_hash(value: &str) -> u64 {
+    value.bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Where in each interval a device should report, in seconds after every multiple of
+/// the interval since the Unix epoch, derived from its MAC
+pub fn slot_offset(mac_address: &str, interval: u64) -> u64 {
+    stable_hash(&mac_address.to_uppercase()) % interval.max(1)
+}
+
+/// Seconds from `now` until the device's next slot. A device already at or just
+/// short of its slot is sent to the following one, so it does not report twice.
+pub fn until_slot(now: DateTime<Utc>, offset: u64, interval: u64) -> u64 {
+    let interval = interval.max(1);
+    let phase = now.timestamp().rem_euclid(interval as i64) as u64;
+    let delay = (offset + interval - phase) % interval;
+    if (delay as f64) <= interval as f64 * SLOT_TOLERANCE {
+        delay + interval
+    } else {
+        delay
+    }
+}
+
+/// How evenly heartbeats arrived across their intervals during one window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlotStats {
+    pub window_started_at: Option<DateTime<Utc>>,
+    pub arrivals: u64,
+    /// Share of arrivals within 5% of the interval from their device's slot
+    pub on_slot: f64,
+    /// Arrivals in the busiest bucket over the mean; 1.0 is perfectly even
+    pub peak_to_mean: f64,
+    /// Arrivals by position within the interval, in 1/60ths of the interval
+    pub buckets: Vec<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlotMetrics {
+    /// The window in progress
+    pub current: SlotStats,
+    /// The last complete window, once there is one
+    pub previous: Option<SlotStats>,
+}
+
+#[derive(Debug, Clone)]
+struct SlotWindow {
+    index: i64,
+    arrivals: u64,
+    on_slot: u64,
+    buckets: [u64; SLOT_BUCKETS],
+}
+
+impl SlotWindow {
+    fn new(index: i64) -> Self {
+        Self { index, arrivals: 0, on_slot: 0, buckets: [0; SLOT_BUCKETS] }
+    }
+
+    fn stats(&self) -> SlotStats {
+        let mean = self.arrivals as f64 / SLOT_BUCKETS as f64;
+        let peak = self.buckets.iter().copied().max().unwrap_or(0) as f64;
+        SlotStats {
+            window_started_at: Utc.timestamp_opt(self.index * SLOT_WINDOW_SECS, 0).single(),
+            arrivals: self.arrivals,
+            on_slot: if self.arrivals == 0 { 0.0 } else { self.on_slot as f64 / self.arrivals as f64 },
+            peak_to_mean: if self.arrivals == 0 { 0.0 } else { peak / mean },
+            buckets: self.buckets.to_vec(),
+        }
+    }
+}
+
+/// Distribution of heartbeat arrivals within their intervals, to spot devices
+/// reporting in lockstep (after a site power cut, say) and check they spread out
+pub struct SlotTracker {
+    windows: Mutex<(SlotWindow, Option<SlotWindow>)>,
+}
+
+impl SlotTracker {
+    pub fn new() -> Self {
+        Self { windows: Mutex::new((SlotWindow::new(0), None)) }
+    }
+
+    pub fn record(&self, now: DateTime<Utc>, offset: u64, interval: u64) {
+        let interval = interval.max(1);
+        let index = now.timestamp().div_euclid(SLOT_WINDOW_SECS);
+        let mut windows = self.windows.lock().unwrap();
+        if windows.0.index != index {
+            let finished = std::mem::replace(&mut windows.0, SlotWindow::new(index));
+            windows.1 = (finished.arrivals > 0).then_some(finished);
+        }
+        let phase = now.timestamp().rem_euclid(interval as i64) as u64;
+        let distance = phase.abs_diff(offset % interval);
+        let distance = distance.min(interval - distance);
+        let current = &mut windows.0;
+        current.arrivals += 1;
+        current.buckets[(phase * SLOT_BUCKETS as u64 / interval) as usize] += 1;
+        if (distance as f64) <= interval as f64 * SLOT_TOLERANCE {
+            current.on_slot += 1;
+        }
+    }
+
+    pub fn metrics(&self) -> SlotMetrics {
+        let windows = self.windows.lock().unwrap();
+        SlotMetrics {
+            current: windows.0.stats(),
+            previous: windows.1.as_ref().map(SlotWindow::stats),
+        }
+    }
+}
+
+/// Heartbeat arrival distribution across intervals (fleet-wide admin tokens only)
+pub async fn get_slot_metrics(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<SlotMetrics>, ApiError> {
+    principal.require_global()?;
+    Ok(Json(state.slots.metrics()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CacheConfig {
+        CacheConfig {
+            default_interval: 300,
+            max_interval: 3600,
+            target_heartbeat_rate: 100,
+            ..CacheConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_interval_resolution_and_load() {
+        let config = config();
+        assert_eq!(expected_interval(Some(60), Some(120), &config), 60);
+        assert_eq!(expected_interval(None, Some(120), &config), 120);
+        assert_eq!(expected_interval(None, None, &config), 300);
+        assert_eq!(expected_interval(Some(7200), None, &config), 3600);
+
+        assert_eq!(effective_interval(300, 50.0, &config), 300);
+        assert_eq!(effective_interval(300, 250.0, &config), 750);
+        assert_eq!(effective_interval(300, 10_000.0, &config), 3600);
+        assert_eq!(effective_interval(300, 250.0, &CacheConfig { adaptive_intervals: false, ..config }), 300);
+
+        assert_eq!(stale_after(None, 600), 600);
+        assert_eq!(stale_after(Some(300), 600), 600);
+        assert_eq!(stale_after(Some(750), 600), 1500);
+
+        let monitor = LoadMonitor::new();
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        for second in 0..120 {
+            monitor.record(start + chrono::Duration::seconds(second / 2));
+        }
+        assert_eq!(monitor.record(start + chrono::Duration::seconds(60)), 2.0);
+        assert_eq!(monitor.record(start + chrono::Duration::seconds(185)), 0.0);
+    }
+
+    #[test]
+    fn test_slots_spread_devices() {
+        assert_eq!(slot_offset("aa:bb:cc:00:00:01", 300), slot_offset("AA:BB:CC:00:00:01", 300));
+        let offsets: std::collections::BTreeSet<u64> = (0..100)
+            .map(|n| slot_offset(&format!("AA:BB:CC:00:00:{:02X}", n), 300))
+            .collect();
+        assert!(offsets.len() > 70);
+
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(until_slot(start, 100, 300), 100);
+        assert_eq!(until_slot(start + chrono::Duration::seconds(95), 100, 300), 305);
+        assert_eq!(until_slot(start + chrono::Duration::seconds(101), 100, 300), 299);
+
+        // Every device reporting at once lands in one bucket
+        let tracker = SlotTracker::new();
+        for n in 0..60 {
+            tracker.record(start + chrono::Duration::seconds(10), slot_offset(&format!("{}", n), 300), 300);
+        }
+        let lockstep = tracker.metrics().current;
+        assert_eq!(lockstep.arrivals, 60);
+        assert_eq!(lockstep.peak_to_mean, 60.0);
+
+        // An hour later, devices reporting on their slots are spread out
+        let later = start + chrono::Duration::seconds(SLOT_WINDOW_SECS);
+        for offset in (0..300).step_by(5) {
+            tracker.record(later + chrono::Duration::seconds(offset as i64), offset, 300);
+        }
+        let metrics = tracker.metrics();
+        assert_eq!(metrics.previous, Some(lockstep));
+        assert_eq!(metrics.current.on_slot, 1.0);
+        assert_eq!(metrics.current.peak_to_mean, 1.0);
+    }
+}
@@ -0,0 +1,367 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::write_queue::PendingWrite;
+
+const SEGMENT_PREFIX: &str = "segment-";
+const SEGMENT_SUFFIX: &str = ".log";
+const ACK_FILE: &str = "ACK";
+/// Frame header: payload length (u32 LE) followed by CRC32 of the payload (u32 LE)
+const FRAME_HEADER_LEN: usize = 8;
+
+/// A single journaled write
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub seq: u64,
+    pub write: PendingWrite,
+}
+
+#[derive(Debug)]
+struct Segment {
+    id: u64,
+    /// Highest sequence number stored in this segment, None when empty
+    last_seq: Option<u64>,
+}
+
+#[derive(Debug)]
+struct JournalInner {
+    /// Closed segments, oldest first
+    sealed: Vec<Segment>,
+    active: Segment,
+    active_file: File,
+    active_size: u64,
+    next_seq: u64,
+    acked_seq: u64,
+}
+
+/// Append-only, segmented on-disk journal of writes pending for MySQL.
+/// Every record is framed with its length and a CRC32 so a torn write at the
+/// tail of a segment (crash mid-append) is detected and discarded on recovery.
+#[derive(Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    segment_size: u64,
+    sync_writes: bool,
+    inner: Mutex<JournalInner>,
+}
+
+impl Journal {
+    /// Open (or create) the journal in `dir`, returning it together with every
+    /// record that was written but never acknowledged, oldest first
+    pub fn open<P: AsRef<Path>>(dir: P, segment_size: u64, sync_writes: bool) -> Result<(Self, Vec<JournalRecord>)> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create journal directory: {:?}", dir))?;
+
+        let acked_seq = read_ack(&dir)?;
+        let mut segment_ids = list_segments(&dir)?;
+        segment_ids.sort_unstable();
+
+        let mut pending = Vec::new();
+        let mut sealed = Vec::new();
+        let mut max_seq = acked_seq;
+        for id in &segment_ids {
+            let records = read_segment(&segment_path(&dir, *id))?;
+            let last_seq = records.last().map(|r| r.seq);
+            if let Some(seq) = last_seq {
+                max_seq = max_seq.max(seq);
+            }
+            pending.extend(records.into_iter().filter(|r| r.seq > acked_seq));
+            sealed.push(Segment { id: *id, last_seq });
+        }
+
+        // Always start appending to a fresh segment
+        let active_id = segment_ids.last().map(|id| id + 1).unwrap_or(0);
+        let active_file = create_segment(&dir, active_id)?;
+
+        let journal = Self {
+            dir,
+            segment_size: segment_size.max(FRAME_HEADER_LEN as u64 + 1),
+            sync_writes,
+            inner: Mutex::new(JournalInner {
+                sealed,
+                active: Segment { id: active_id, last_seq: None },
+                active_file,
+                active_size: 0,
+                next_seq: max_seq + 1,
+                acked_seq,
+            }),
+        };
+        journal.compact()?;
+
+        if !pending.is_empty() {
+            log::info!("Recovered {} unacknowledged writes from journal {:?}", pending.len(), journal.dir);
+        }
+        Ok((journal, pending))
+    }
+
+    /// Append a write, returning its sequence number
+    pub fn append(&self, write: &PendingWrite) -> Result<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        let seq = inner.next_seq;
+        let record = JournalRecord { seq, write: write.clone() };
+        let payload = serde_json::to_vec(&record).context("Failed to serialize journal record")?;
+
+        if inner.active_size > 0 && inner.active_size + (FRAME_HEADER_LEN + payload.len()) as u64 > self.segment_size {
+            self.rotate(&mut inner)?;
+        }
+
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        frame.extend_from_slice(&payload);
+
+        inner.active_file.write_all(&frame).context("Failed to append to journal")?;
+        if self.sync_writes {
+            inner.active_file.sync_data().context("Failed to sync journal")?;
+        }
+        inner.active_size += frame.len() as u64;
+        inner.active.last_seq = Some(seq);
+        inner.next_seq += 1;
+        Ok(seq)
+    }
+
+    /// Mark every record up to and including `seq` as applied to MySQL
+    pub fn acknowledge(&self, seq: u64) -> Result<()> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if seq <= inner.acked_seq {
+                return Ok(());
+            }
+            inner.acked_seq = seq;
+            write_ack(&self.dir, seq)?;
+        }
+        self.compact()
+    }
+
+    /// Unacknowledged records from `seq` on, oldest first, at most `limit` of them.
+    /// Reads back writes that did not fit in the in-memory queue.
+    pub fn read_from(&self, seq: u64, limit: usize) -> Result<Vec<JournalRecord>> {
+        // Holding the lock keeps appends from leaving a partial frame to read
+        let inner = self.inner.lock().unwrap();
+        let mut records = Vec::new();
+        for segment in inner.sealed.iter().chain(std::iter::once(&inner.active)) {
+            if segment.last_seq.is_none_or(|last| last < seq) {
+                continue;
+            }
+            for record in read_segment(&segment_path(&self.dir, segment.id))? {
+                if record.seq >= seq && record.seq > inner.acked_seq {
+                    records.push(record);
+                    if records.len() >= limit {
+                        return Ok(records);
+                    }
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    /// Delete segments whose records have all been acknowledged
+    pub fn compact(&self) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let acked_seq = inner.acked_seq;
+        let fully_acked = |segment: &Segment| segment.last_seq.is_none_or(|seq| seq <= acked_seq);
+
+        // Seal a fully acknowledged active segment so it can be removed too
+        if inner.active.last_seq.is_some() && fully_acked(&inner.active) {
+            self.rotate(&mut inner)?;
+        }
+
+        let mut removed = 0;
+        while let Some(segment) = inner.sealed.first() {
+            if !fully_acked(segment) {
+                break;
+            }
+            let path = segment_path(&self.dir, segment.id);
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove journal segment: {:?}", path))?;
+            inner.sealed.remove(0);
+            removed += 1;
+        }
+        if removed > 0 {
+            log::debug!("Compacted {} acknowledged journal segments", removed);
+        }
+        Ok(())
+    }
+
+    /// Number of journaled records not yet acknowledged
+    pub fn pending_count(&self) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        inner.next_seq - 1 - inner.acked_seq
+    }
+
+    fn rotate(&self, inner: &mut JournalInner) -> Result<()> {
+        if self.sync_writes {
+            inner.active_file.sync_all().context("Failed to sync journal segment")?;
+        }
+        let next_id = inner.active.id + 1;
+        let file = create_segment(&self.dir, next_id)?;
+        let previous = std::mem::replace(&mut inner.active, Segment { id: next_id, last_seq: None });
+        inner.sealed.push(previous);
+        inner.active_file = file;
+        inner.active_size = 0;
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{}{:016}{}", SEGMENT_PREFIX, id, SEGMENT_SUFFIX))
+}
+
+fn list_segments(dir: &Path) -> Result<Vec<u64>> {
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read journal directory: {:?}", dir))? {
+        let name = entry?.file_name();
+        let name = name.to_string_lossy();
+        if let Some(id) = name.strip_prefix(SEGMENT_PREFIX)
+            .and_then(|rest| rest.strip_suffix(SEGMENT_SUFFIX))
+            .and_then(|id| id.parse().ok())
+        {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+fn create_segment(dir: &Path, id: u64) -> Result<File> {
+    let path = segment_path(dir, id);
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open journal segment: {:?}", path))
+}
+
+/// Read all valid records from a segment, truncating a torn or corrupt tail
+fn read_segment(path: &Path) -> Result<Vec<JournalRecord>> {
+    let mut data = Vec::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut data))
+        .with_context(|| format!("Failed to read journal segment: {:?}", path))?;
+
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset + FRAME_HEADER_LEN <= data.len() {
+        let len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap());
+        let start = offset + FRAME_HEADER_LEN;
+        let Some(payload) = data.get(start..start + len) else { break };
+        if crc32fast::hash(payload) != crc {
+            break;
+        }
+        match serde_json::from_slice::<JournalRecord>(payload) {
+            Ok(record) => records.push(record),
+            Err(_) => break,
+        }
+        offset = start + len;
+    }
+
+    if offset < data.len() {
+        log::warn!("Discarding {} bytes of corrupt or incomplete data at the end of {:?}",
+            data.len() - offset, path);
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|f| f.set_len(offset as u64))
+            .with_context(|| format!("Failed to truncate journal segment: {:?}", path))?;
+    }
+    Ok(records)
+}
+
+fn read_ack(dir: &Path) -> Result<u64> {
+    match fs::read_to_string(dir.join(ACK_FILE)) {
+        Ok(content) => content.trim().parse()
+            .with_context(|| format!("Invalid journal ACK file in {:?}", dir)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e).with_context(|| format!("Failed to read journal ACK file in {:?}", dir)),
+    }
+}
+
+/// Persist the acknowledged sequence atomically (write then rename)
+fn write_ack(dir: &Path, seq: u64) -> Result<()> {
+    let tmp = dir.join(format!("{}.tmp", ACK_FILE));
+    let mut file = File::create(&tmp).context("Failed to write journal ACK file")?;
+    file.write_all(seq.to_string().as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, dir.join(ACK_FILE)).context("Failed to replace journal ACK file")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hbd-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn heartbeat(mac: &str) -> PendingWrite {
+        PendingWrite::Heartbeat {
+            mac_address: mac.to_string(),
+            local_ip_address: "192.168.1.10".to_string(),
+            global_ip_address: "203.0.113.5".to_string(),
+            received_at: Utc::now(),
+            telemetry: None,
+            interval: None,
+        }
+    }
+
+    #[test]
+    fn test_recovers_unacknowledged_records() {
+        let dir = test_dir("recover");
+        {
+            let (journal, pending) = Journal::open(&dir, 1024 * 1024, false).unwrap();
+            assert!(pending.is_empty());
+            journal.append(&heartbeat("AA")).unwrap();
+            let seq = journal.append(&heartbeat("BB")).unwrap();
+            journal.append(&heartbeat("CC")).unwrap();
+            journal.acknowledge(seq - 1).unwrap();
+        }
+        let (journal, pending) = Journal::open(&dir, 1024 * 1024, false).unwrap();
+        let macs: Vec<_> = pending.iter().map(|r| r.write.mac_address().to_string()).collect();
+        assert_eq!(macs, vec!["BB", "CC"]);
+        assert_eq!(journal.append(&heartbeat("DD")).unwrap(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_discards_torn_tail() {
+        let dir = test_dir("torn");
+        {
+            let (journal, _) = Journal::open(&dir, 1024 * 1024, false).unwrap();
+            journal.append(&heartbeat("AA")).unwrap();
+        }
+        let segment = segment_path(&dir, 0);
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let (_, pending) = Journal::open(&dir, 1024 * 1024, false).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].write.mac_address(), "AA");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compacts_acknowledged_segments() {
+        let dir = test_dir("compact");
+        let (journal, _) = Journal::open(&dir, 64, false).unwrap();
+        let mut last = 0;
+        for mac in ["AA", "BB", "CC", "DD"] {
+            last = journal.append(&heartbeat(mac)).unwrap();
+        }
+        assert!(list_segments(&dir).unwrap().len() > 1);
+        journal.acknowledge(last).unwrap();
+        assert_eq!(journal.pending_count(), 0);
+        // Only the fresh, empty active segment remains
+        assert_eq!(list_segments(&dir).unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
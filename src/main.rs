@@ -13,6 +13,33 @@ mod server;
 mod config;
 mod app_with_mysql_and_cache;
 mod cache;
+mod backoff;
+mod circuit_breaker;
+mod journal;
+mod replicas;
+mod migrations;
+mod history;
+mod reports;
+mod cleanup;
+mod devices;
+mod users;
+mod error;
+mod auth;
+mod accounts;
+mod metrics;
+mod squelch;
+mod zones;
+mod events;
+mod alerts;
+mod webhooks;
+mod outbox;
+mod broker;
+mod stream;
+mod maintenance;
+mod intervals;
+mod telemetry;
+mod firmware;
+mod write_queue;
 
 // Custom syslog writer
 struct SyslogWriter {
@@ -165,6 +192,66 @@ async fn start_http_server(mut syslog_writer: Option<SyslogWriter>, config: &con
     // Create application state
     let state = server::AppState::new()?;
     
+    // Connect once the server is up, then probe MySQL and replay queued writes while degraded
+    log_both!(syslog_writer, "info", "Connecting to MySQL in the background; /ready returns 503 until connected");
+    let startup = tokio::spawn(app_with_mysql_and_cache::connect_at_startup(state.clone()));
+    
+    // Flush heartbeat history to MySQL
+    if config.history.enabled {
+        let interval = std::time::Duration::from_secs(config.history.flush_interval);
+        tokio::spawn(history::run_history_flush(state.clone(), interval));
+    }
+    
+    // Reload API-managed squelches so every instance honours them
+    let squelch_interval = std::time::Duration::from_secs(config.squelch.refresh_interval);
+    tokio::spawn(squelch::run_squelch_refresh(state.clone(), squelch_interval));
+
+    // Reload maintenance windows so every instance keeps their events from the sinks
+    let maintenance_interval = std::time::Duration::from_secs(config.maintenance.refresh_interval);
+    tokio::spawn(maintenance::run_maintenance_refresh(state.clone(), maintenance_interval));
+
+    // Reload firmware campaigns and pause those whose devices go offline
+    let firmware_interval = std::time::Duration::from_secs(config.firmware.check_interval);
+    tokio::spawn(firmware::run_campaign_monitor(state.clone(), firmware_interval));
+    
+    // Watch for device and site outages
+    if config.alerts.enabled {
+        let interval = std::time::Duration::from_secs(config.alerts.check_interval);
+        tokio::spawn(alerts::run_outage_monitor(state.clone(), interval));
+    }
+    
+    // Relay outbox events to the event bus and webhook delivery log
+    tokio::spawn(outbox::run_outbox_relay(state.clone()));
+
+    // Deliver events to registered webhooks
+    if config.webhooks.enabled {
+        tokio::spawn(webhooks::run_webhook_delivery(state.clone()));
+    }
+
+    // Publish events to NATS
+    if config.broker.enabled {
+        log_both!(syslog_writer, "info", "Publishing events to {} as {:?}", config.broker.url, config.broker.format);
+        tokio::spawn(broker::run_broker_publisher(state.clone(), config.broker.clone()));
+    }
+    
+    // Purge old heartbeat history and stale cache entries
+    let cache_config = config.app.cache.clone().unwrap_or_default();
+    if cache_config.auto_cleanup {
+        log_both!(syslog_writer, "info", "Automatic cleanup every {}h of records older than {} days",
+            cache_config.cleanup_interval, cache_config.max_record_age);
+        tokio::spawn(cleanup::run_cleanup(state.clone(), cache_config));
+    }
+    
+    // Health check read replicas
+    if !state.replicas.is_empty() {
+        let interval = std::time::Duration::from_secs(config.database.replica_health_check_interval);
+        tokio::spawn(state.replicas.clone().run_health_checks(interval));
+    }
+    
+    if config.server.admin_tokens.is_empty() && config.server.account_tokens.is_empty() {
+        log_both!(syslog_writer, "warn", "No server.admin_tokens or account_tokens configured, admin routes only accept user tokens");
+    }
+    
     // Create the router
     let app = server::create_router(state);
     
@@ -181,16 +268,49 @@ async fn start_http_server(mut syslog_writer: Option<SyslogWriter>, config: &con
     log_both!(syslog_writer, "info", "📡 Server listening on http://{}", bind_address);
     log_both!(syslog_writer, "info", "Available endpoints:");
     log_both!(syslog_writer, "info", "  GET  /health           - Health check");
+    log_both!(syslog_writer, "info", "  GET  /ready            - Readiness check (503 until MySQL is connected)");
     log_both!(syslog_writer, "info", "  GET  /api/db-info      - Database information");
     log_both!(syslog_writer, "info", "  GET  /hbd              - Device heartbeat endpoint (supports ?ID=123&MAC=000&IP=192.168.1.1&ts=1749862684)");
+    log_both!(syslog_writer, "info", "  POST /hbd              - Heartbeat with telemetry (firmware_version, uptime, cpu_temp, free_storage, rssi) as JSON body or query");
+    log_both!(syslog_writer, "info", "  GET  /api/devices      - List devices (?zone=&camera=&status=&last_seen_after=&limit=&offset=) (admin)");
+    log_both!(syslog_writer, "info", "  POST /api/devices      - Register a device (admin)");
+    log_both!(syslog_writer, "info", "  GET|PUT|DELETE /api/devices/:mac - Get, update or deactivate a device (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/devices/:mac/telemetry - Latest telemetry reported by a device (admin)");
+    log_both!(syslog_writer, "info", "  GET|POST /api/users    - List (?limit=&offset=) or create operator accounts (admin)");
+    log_both!(syslog_writer, "info", "  GET|DELETE /api/users/:id - Get or delete an operator account (admin)");
+    log_both!(syslog_writer, "info", "  POST /api/heartbeat/procedure - Stored procedure test (admin)");
+    log_both!(syslog_writer, "info", "  GET|PUT /api/accounts/:account/settings - Per-account redirect, interval and rate limit (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/topology     - Zones with their cameras' live status (admin)");
+    log_both!(syslog_writer, "info", "  POST /api/zones        - Register a zone (admin)");
+    log_both!(syslog_writer, "info", "  GET|PUT|DELETE /api/zones/:zone - Zone topology, update or delete (admin)");
+    log_both!(syslog_writer, "info", "  POST|DELETE /api/{{devices/:mac,zones/:zone,accounts/:account}}/squelch - Squelch (optional starts_at, ends_at, reason) or unsquelch (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/squelches    - List pending squelches (?all=true for lifted and expired) (admin)");
+    log_both!(syslog_writer, "info", "  GET|POST /api/maintenance - List or create maintenance windows (one-off or cron schedule) (admin)");
+    log_both!(syslog_writer, "info", "  GET|DELETE /api/maintenance/:id - Get or delete a maintenance window (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/firmware     - Devices per reported firmware version (?zone=&account=) (admin)");
+    log_both!(syslog_writer, "info", "  GET|POST /api/firmware/campaigns - List or start firmware rollouts (version, url, percentage, zones) (admin)");
+    log_both!(syslog_writer, "info", "  GET|PUT /api/firmware/campaigns/:id - Campaign progress, or change percentage/status (admin)");
+    log_both!(syslog_writer, "info", "  GET|POST /api/webhooks - List or register webhooks (url, secret, event_types) (admin)");
+    log_both!(syslog_writer, "info", "  GET|DELETE /api/webhooks/:id - Get or delete a webhook (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/webhooks/:id/deliveries - Delivery log (?status=pending|delivered|dead) (admin)");
+    log_both!(syslog_writer, "info", "  POST /api/deliveries/:id/retry - Requeue a dead-lettered delivery (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/metrics/accounts - Heartbeat counters per account (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/metrics/broker - NATS publishing counters (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/metrics/slots - Heartbeat arrival spread across intervals (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/stream - WebSocket stream of heartbeats and events for subscribed devices, zones or accounts (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/history/:mac - Heartbeat history (?from=&to=&resolution=minute|hour) (admin)");
+    log_both!(syslog_writer, "info", "  GET  /api/reports/uptime/{{devices/:mac,zones/:zone,accounts/:account}} - Uptime/SLA report (?from=&to=&format=json|csv) (admin)");
     log_both!(syslog_writer, "info", "🔥 Press Ctrl+C to stop the server");
     
-    // Start the server with ConnectInfo support
-    axum::serve(
+    // Start the server with ConnectInfo support; stop if MySQL must be up by the deadline and is not
+    let server = axum::serve(
         listener,
         app.into_make_service_with_connect_info::<std::net::SocketAddr>()
-    ).await
-        .map_err(|e| anyhow::Error::msg(format!("Server error: {}", e)))?;
+    ).into_future();
+    tokio::select! {
+        result = server => result.map_err(|e| anyhow::Error::msg(format!("Server error: {}", e)))?,
+        Ok(Err(e)) = startup => return Err(e),
+    }
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,409 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDateTime, Utc};
+use croner::Cron;
+use mysql::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::events::DeviceEvent;
+use crate::server::AppState;
+use crate::squelch::SquelchScope;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Longest single occurrence of a recurring window
+const MAX_DURATION_MINUTES: u32 = 7 * 24 * 60;
+
+const WINDOW_COLUMNS: &str = "id, scope, mac_address, zone_number, account_id,
+    DATE_FORMAT(starts_at, '%Y-%m-%d %H:%i:%s'), DATE_FORMAT(ends_at, '%Y-%m-%d %H:%i:%s'),
+    schedule, duration_minutes, reason, DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s')";
+
+/// Row shape produced by WINDOW_COLUMNS
+type WindowRow = (u64, String, Option<String>, Option<i32>, Option<i32>, Option<String>, Option<String>,
+    Option<String>, Option<u32>, Option<String>, Option<String>);
+
+/// A maintenance window on a device, zone or account. One-off windows run from
+/// starts_at to ends_at; recurring windows open at every match of the cron `schedule`
+/// for `duration_minutes`, optionally only between starts_at and ends_at.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MaintenanceWindow {
+    pub id: u64,
+    pub scope: SquelchScope,
+    pub mac_address: Option<String>,
+    pub zone_number: Option<i32>,
+    pub account_id: Option<i32>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    /// Five-field cron expression in UTC, e.g. "0 2 * * SUN"
+    pub schedule: Option<String>,
+    pub duration_minutes: Option<u32>,
+    pub reason: Option<String>,
+    pub created_at: Option<String>,
+}
+
+impl MaintenanceWindow {
+    /// True if `at` falls inside the window or one of its occurrences
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        if self.starts_at.is_some_and(|start| at < start) || self.ends_at.is_some_and(|end| at >= end) {
+            return false;
+        }
+        let (Some(schedule), Some(minutes)) = (&self.schedule, self.duration_minutes) else {
+            return self.starts_at.is_some();
+        };
+        let Ok(cron) = parse_schedule(schedule) else {
+            return false;
+        };
+        // The earliest occurrence still running at `at` started within the last `duration`
+        let duration = ChronoDuration::minutes(minutes as i64);
+        cron.find_next_occurrence(&(at - duration), false)
+            .is_ok_and(|occurrence| occurrence <= at && at < occurrence + duration)
+    }
+
+    /// True if the window covers a device or site with this MAC, zone and account
+    pub fn applies_to(&self, mac: Option<&str>, zone: Option<i32>, account: Option<i32>) -> bool {
+        match self.scope {
+            SquelchScope::Device => self.mac_address.as_deref()
+                .is_some_and(|m| mac.is_some_and(|mac| m.eq_ignore_ascii_case(mac))),
+            SquelchScope::Zone => self.zone_number.is_some() && self.zone_number == zone
+                && self.account_id.is_none_or(|a| Some(a) == account),
+            SquelchScope::Account => self.account_id.is_some() && self.account_id == account,
+        }
+    }
+}
+
+fn parse_schedule(schedule: &str) -> Result<Cron, croner::errors::CronError> {
+    Cron::new(schedule).parse()
+}
+
+fn parse_db_datetime(value: Option<String>) -> Option<DateTime<Utc>> {
+    value.and_then(|v| NaiveDateTime::parse_from_str(&v, DB_DATETIME_FORMAT).ok()).map(|dt| dt.and_utc())
+}
+
+fn window_from_row(row: mysql::Row) -> Option<MaintenanceWindow> {
+    let (id, scope, mac_address, zone_number, account_id, starts_at, ends_at, schedule, duration_minutes, reason,
+        created_at): WindowRow = mysql::from_row(row);
+    Some(MaintenanceWindow {
+        id,
+        scope: SquelchScope::parse(&scope)?,
+        mac_address,
+        zone_number,
+        account_id,
+        starts_at: parse_db_datetime(starts_at),
+        ends_at: parse_db_datetime(ends_at),
+        schedule,
+        duration_minutes,
+        reason,
+        created_at,
+    })
+}
+
+/// In-memory copy of the maintenance windows that have not ended, or ended within
+/// outbox.retention so events relayed late are still suppressed, reloaded
+/// periodically so windows created through any hbd instance are honoured
+pub struct MaintenanceSet {
+    windows: RwLock<Vec<MaintenanceWindow>>,
+}
+
+impl MaintenanceSet {
+    pub fn new() -> Self {
+        Self { windows: RwLock::new(Vec::new()) }
+    }
+
+    pub fn replace(&self, windows: Vec<MaintenanceWindow>) {
+        *self.windows.write().unwrap() = windows;
+    }
+
+    /// Load the windows still open or closed less than `keep_ended_hours` ago
+    pub fn reload(&self, conn: &mut mysql::PooledConn, keep_ended_hours: u64) -> mysql::Result<usize> {
+        let rows: Vec<mysql::Row> = conn.exec(format!(
+            "SELECT {} FROM maintenance_windows WHERE ends_at IS NULL OR ends_at > UTC_TIMESTAMP() - INTERVAL ? HOUR",
+            WINDOW_COLUMNS
+        ), (keep_ended_hours,))?;
+        let windows: Vec<MaintenanceWindow> = rows.into_iter().filter_map(window_from_row).collect();
+        let count = windows.len();
+        self.replace(windows);
+        Ok(count)
+    }
+
+    /// The window covering an event at the time it happened, if any.
+    /// Site events match zone and account windows.
+    pub fn covering(&self, event: &DeviceEvent) -> Option<MaintenanceWindow> {
+        self.windows.read().unwrap().iter()
+            .find(|window| window.applies_to(event.mac_address.as_deref(), event.zone_number, event.account_id)
+                && window.is_active(event.at))
+            .cloned()
+    }
+}
+
+/// Background task: reload maintenance windows from MySQL
+pub async fn run_maintenance_refresh(state: AppState, interval: Duration) {
+    loop {
+        let refresh_state = state.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut conn = refresh_state.get_read_connection()?;
+            refresh_state.maintenance.reload(&mut conn, refresh_state.config.outbox.retention)
+                .map_err(anyhow::Error::from)
+        }).await;
+        match result {
+            Ok(Ok(count)) => log::debug!("Loaded {} maintenance windows", count),
+            Ok(Err(e)) => log::debug!("Maintenance window reload failed, keeping previous set: {:#}", e),
+            Err(e) => log::error!("Maintenance window reload task panicked: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMaintenanceRequest {
+    pub scope: SquelchScope,
+    /// Device MAC for device windows
+    pub mac_address: Option<String>,
+    /// Zone for zone windows
+    pub zone_number: Option<i32>,
+    /// Account for account windows; account tokens default to their own
+    pub account_id: Option<i32>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    /// Cron expression (UTC) opening a recurring window; requires duration_minutes
+    pub schedule: Option<String>,
+    pub duration_minutes: Option<u32>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceQuery {
+    /// Include windows that have ended (default: false)
+    pub all: Option<bool>,
+}
+
+/// A window with whether it is open right now
+#[derive(Debug, Serialize)]
+pub struct MaintenanceWindowResponse {
+    #[serde(flatten)]
+    pub window: MaintenanceWindow,
+    pub active: bool,
+}
+
+impl From<MaintenanceWindow> for MaintenanceWindowResponse {
+    fn from(window: MaintenanceWindow) -> Self {
+        let active = window.is_active(Utc::now());
+        Self { window, active }
+    }
+}
+
+/// Check the schedule fields of a new window
+fn validate_schedule(request: &CreateMaintenanceRequest, now: DateTime<Utc>) -> Result<(), ApiError> {
+    if let (Some(start), Some(end)) = (request.starts_at, request.ends_at)
+        && start >= end
+    {
+        return Err(ApiError::bad_request("ends_at must be after starts_at"));
+    }
+    if request.ends_at.is_some_and(|end| end <= now) {
+        return Err(ApiError::bad_request("ends_at must be in the future"));
+    }
+    match (&request.schedule, request.duration_minutes) {
+        (Some(schedule), Some(minutes)) => {
+            parse_schedule(schedule).map_err(|e| ApiError::bad_request(format!("Invalid schedule: {}", e)))?;
+            if minutes == 0 || minutes > MAX_DURATION_MINUTES {
+                return Err(ApiError::bad_request(format!("duration_minutes must be between 1 and {}", MAX_DURATION_MINUTES)));
+            }
+        },
+        (None, None) => {
+            if request.starts_at.is_none() || request.ends_at.is_none() {
+                return Err(ApiError::bad_request("One-off windows need starts_at and ends_at"));
+            }
+        },
+        _ => return Err(ApiError::bad_request("schedule and duration_minutes must be given together")),
+    }
+    Ok(())
+}
+
+fn find_window(conn: &mut mysql::PooledConn, principal: &Principal, id: u64) -> Result<MaintenanceWindow, ApiError> {
+    let row: Option<mysql::Row> = conn.exec_first(
+        format!("SELECT {} FROM maintenance_windows WHERE id = ?", WINDOW_COLUMNS),
+        (id,),
+    ).map_err(|e| ApiError::database("Failed to get maintenance window", e))?;
+    row.and_then(window_from_row)
+        .filter(|window| principal.can_access(window.account_id))
+        .ok_or_else(|| ApiError::not_found(format!("Maintenance window {} not found", id)))
+}
+
+fn reload_windows(state: &AppState, conn: &mut mysql::PooledConn) {
+    if let Err(e) = state.maintenance.reload(conn, state.config.outbox.retention) {
+        log::warn!("Failed to reload maintenance windows: {}", e);
+    }
+}
+
+/// Create a maintenance window
+pub async fn create_window(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<CreateMaintenanceRequest>,
+) -> Result<(StatusCode, Json<MaintenanceWindowResponse>), ApiError> {
+    validate_schedule(&request, Utc::now())?;
+
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    let (mac_address, zone_number, account_id) = match request.scope {
+        SquelchScope::Device => {
+            let mac = request.mac_address.as_deref().ok_or_else(|| ApiError::bad_request("mac_address is required"))?;
+            let device = crate::devices::find_device(&mut conn, mac, 0)
+                .map_err(|e| ApiError::database("Failed to look up device", e))?
+                .filter(|device| principal.can_access(device.account_id))
+                .ok_or_else(|| ApiError::not_found(format!("Device {} not found", mac)))?;
+            (Some(device.mac_address), None, device.account_id)
+        },
+        SquelchScope::Zone => {
+            let zone = request.zone_number.ok_or_else(|| ApiError::bad_request("zone_number is required"))?;
+            // Account tokens only cover their own devices in the zone
+            (None, Some(zone), principal.account_id)
+        },
+        SquelchScope::Account => {
+            let account = request.account_id.or(principal.account_id)
+                .ok_or_else(|| ApiError::bad_request("account_id is required"))?;
+            principal.authorize(Some(account))?;
+            (None, None, Some(account))
+        },
+    };
+
+    conn.exec_drop(
+        "INSERT INTO maintenance_windows
+            (scope, mac_address, zone_number, account_id, starts_at, ends_at, schedule, duration_minutes, reason)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+            request.scope.as_str(),
+            &mac_address,
+            zone_number,
+            account_id,
+            request.starts_at.map(|t| t.format(DB_DATETIME_FORMAT).to_string()),
+            request.ends_at.map(|t| t.format(DB_DATETIME_FORMAT).to_string()),
+            &request.schedule,
+            request.duration_minutes,
+            &request.reason,
+        ),
+    ).map_err(|e| ApiError::database("Failed to create maintenance window", e))?;
+    let id = conn.last_insert_id();
+    reload_windows(&state, &mut conn);
+
+    log::info!("Maintenance window {} on {} created ({:?} - {:?}, schedule {:?} for {:?} minutes): {}",
+        id, request.scope.as_str(), request.starts_at, request.ends_at, request.schedule, request.duration_minutes,
+        request.reason.as_deref().unwrap_or(""));
+    let window = find_window(&mut conn, &principal, id)?;
+    Ok((StatusCode::CREATED, Json(window.into())))
+}
+
+/// List maintenance windows visible to the caller, current and upcoming ones unless `all=true`
+pub async fn list_windows(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<MaintenanceQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut filters = Vec::new();
+    let mut values: Vec<mysql::Value> = Vec::new();
+    if !params.all.unwrap_or(false) {
+        filters.push("(ends_at IS NULL OR ends_at > UTC_TIMESTAMP())");
+    }
+    if let Some(account) = principal.account_id {
+        filters.push("account_id = ?");
+        values.push(account.into());
+    }
+    let where_clause = if filters.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", filters.join(" AND "))
+    };
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let rows: Vec<mysql::Row> = conn.exec(
+        format!("SELECT {} FROM maintenance_windows {} ORDER BY id DESC", WINDOW_COLUMNS, where_clause),
+        values,
+    ).map_err(|e| ApiError::database("Failed to list maintenance windows", e))?;
+    let windows: Vec<MaintenanceWindowResponse> = rows.into_iter().filter_map(window_from_row).map(Into::into).collect();
+    Ok(Json(serde_json::json!({ "windows": windows })))
+}
+
+/// Get a maintenance window
+pub async fn get_window(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u64>,
+) -> Result<Json<MaintenanceWindowResponse>, ApiError> {
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_window(&mut conn, &principal, id).map(|window| Json(window.into()))
+}
+
+/// Delete a maintenance window, ending it immediately
+pub async fn delete_window(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_window(&mut conn, &principal, id)?;
+    conn.exec_drop("DELETE FROM maintenance_windows WHERE id = ?", (id,))
+        .map_err(|e| ApiError::database("Failed to delete maintenance window", e))?;
+    reload_windows(&state, &mut conn);
+    log::info!("Maintenance window {} deleted", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn window(scope: SquelchScope) -> MaintenanceWindow {
+        MaintenanceWindow {
+            id: 1,
+            scope,
+            mac_address: None,
+            zone_number: Some(5),
+            account_id: None,
+            starts_at: None,
+            ends_at: None,
+            schedule: None,
+            duration_minutes: None,
+            reason: None,
+            created_at: None,
+        }
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_one_off_window() {
+        let one_off = MaintenanceWindow { starts_at: Some(at(2, 1, 0)), ends_at: Some(at(2, 3, 0)), ..window(SquelchScope::Zone) };
+        assert!(!one_off.is_active(at(2, 0, 59)));
+        assert!(one_off.is_active(at(2, 1, 0)));
+        assert!(!one_off.is_active(at(2, 3, 0)));
+        assert!(one_off.applies_to(None, Some(5), Some(9)));
+        assert!(!one_off.applies_to(Some("AA"), Some(6), Some(9)));
+    }
+
+    #[test]
+    fn test_recurring_window() {
+        // Sundays 02:00-04:00 UTC; 2026-03-01 is a Sunday
+        let weekly = MaintenanceWindow {
+            schedule: Some("0 2 * * SUN".to_string()),
+            duration_minutes: Some(120),
+            ends_at: Some(at(10, 0, 0)),
+            ..window(SquelchScope::Zone)
+        };
+        assert!(!weekly.is_active(at(1, 1, 59)));
+        assert!(weekly.is_active(at(1, 2, 0)));
+        assert!(weekly.is_active(at(1, 3, 59)));
+        assert!(!weekly.is_active(at(1, 4, 0)));
+        assert!(!weekly.is_active(at(2, 3, 0)));
+        assert!(weekly.is_active(at(8, 2, 30)));
+        // Past ends_at the schedule no longer applies
+        assert!(!weekly.is_active(at(15, 2, 30)));
+    }
+}
@@ -0,0 +1,102 @@
+use axum::{
+    extract::State,
+    response::Json,
+    Extension,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::auth::Principal;
+use crate::server::AppState;
+
+/// Per-account heartbeat counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct AccountCounters {
+    /// Heartbeats accepted
+    pub heartbeats: u64,
+    /// Heartbeats refused as unauthorized or deactivated
+    pub rejected: u64,
+    /// Heartbeats refused by the account rate limit
+    pub rate_limited: u64,
+    /// Heartbeat writes queued because MySQL was unavailable
+    pub queued_writes: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Counter {
+    Heartbeat,
+    Rejected,
+    RateLimited,
+    QueuedWrite,
+}
+
+/// Counters for one account; devices with no account are reported with account_id null
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountMetricsEntry {
+    pub account_id: Option<i32>,
+    #[serde(flatten)]
+    pub counters: AccountCounters,
+}
+
+/// Heartbeat counters attributed to the account of each device
+#[derive(Debug, Default)]
+pub struct AccountMetrics {
+    counters: Mutex<BTreeMap<Option<i32>, AccountCounters>>,
+}
+
+impl AccountMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, account_id: Option<i32>, counter: Counter) {
+        let mut counters = self.counters.lock().unwrap();
+        let entry = counters.entry(account_id).or_default();
+        match counter {
+            Counter::Heartbeat => entry.heartbeats += 1,
+            Counter::Rejected => entry.rejected += 1,
+            Counter::RateLimited => entry.rate_limited += 1,
+            Counter::QueuedWrite => entry.queued_writes += 1,
+        }
+    }
+
+    /// Counters for every account the principal can see
+    pub fn snapshot(&self, principal: &Principal) -> Vec<AccountMetricsEntry> {
+        self.counters.lock().unwrap().iter()
+            .filter(|(account_id, _)| principal.can_access(**account_id))
+            .map(|(account_id, counters)| AccountMetricsEntry { account_id: *account_id, counters: *counters })
+            .collect()
+    }
+}
+
+/// Heartbeat counters per account since hbd started
+pub async fn get_account_metrics(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "accounts": state.metrics.snapshot(&principal)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_are_scoped_to_principal() {
+        let metrics = AccountMetrics::new();
+        metrics.record(Some(1), Counter::Heartbeat);
+        metrics.record(Some(1), Counter::Heartbeat);
+        metrics.record(Some(2), Counter::RateLimited);
+        metrics.record(None, Counter::Rejected);
+
+        let all = metrics.snapshot(&Principal { account_id: None });
+        assert_eq!(all.len(), 3);
+
+        let scoped = metrics.snapshot(&Principal { account_id: Some(1) });
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0].counters.heartbeats, 2);
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use mysql::prelude::*;
+
+/// Schema migrations embedded in the binary, applied in order
+const MIGRATIONS: &[(u32, &str, &str)] = &[
+    (1, "heartbeat_history", include_str!("../migrations/001_heartbeat_history.sql")),
+    (2, "device_registry", include_str!("../migrations/002_device_registry.sql")),
+    (3, "users", include_str!("../migrations/003_users.sql")),
+    (4, "account_settings", include_str!("../migrations/004_account_settings.sql")),
+    (5, "squelches", include_str!("../migrations/005_squelches.sql")),
+    (6, "zones", include_str!("../migrations/006_zones.sql")),
+    (7, "webhooks", include_str!("../migrations/007_webhooks.sql")),
+    (8, "event_outbox", include_str!("../migrations/008_event_outbox.sql")),
+    (9, "maintenance_windows", include_str!("../migrations/009_maintenance_windows.sql")),
+    (10, "heartbeat_intervals", include_str!("../migrations/010_heartbeat_intervals.sql")),
+    (11, "device_telemetry", include_str!("../migrations/011_device_telemetry.sql")),
+    (12, "firmware_campaigns", include_str!("../migrations/012_firmware_campaigns.sql")),
+    (13, "last_interval", include_str!("../migrations/013_last_interval.sql")),
+    (14, "user_tokens", include_str!("../migrations/014_user_tokens.sql")),
+    (15, "outbox_dead_letters", include_str!("../migrations/015_outbox_dead_letters.sql")),
+];
+
+/// Apply any migrations not yet recorded in `schema_migrations`
+pub fn run(pool: &mysql::Pool) -> Result<()> {
+    let mut conn = pool.get_conn().context("Failed to get connection for migrations")?;
+    conn.query_drop(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INT UNSIGNED NOT NULL PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )"
+    ).context("Failed to create schema_migrations table")?;
+
+    let applied: Vec<u32> = conn.query("SELECT version FROM schema_migrations")
+        .context("Failed to read applied migrations")?;
+
+    for (version, name, sql) in MIGRATIONS {
+        if applied.contains(version) {
+            continue;
+        }
+        for statement in sql.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            conn.query_drop(statement)
+                .with_context(|| format!("Migration {:03}_{} failed", version, name))?;
+        }
+        conn.exec_drop(
+            "INSERT INTO schema_migrations (version, name) VALUES (?, ?)",
+            (version, name)
+        )?;
+        log::info!("Applied migration {:03}_{}", version, name);
+    }
+    Ok(())
+}
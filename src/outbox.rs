@@ -0,0 +1,235 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use mysql::prelude::*;
+use mysql::{IsolationLevel, TxOpts};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::events::DeviceEvent;
+use crate::maintenance::MaintenanceSet;
+use crate::server::AppState;
+use crate::write_queue::PendingWrite;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Longest error stored for a dead-lettered row (event_outbox.error)
+const MAX_ERROR_LEN: usize = 1024;
+
+/// Wakes the relay when events are written to the outbox
+pub struct Outbox {
+    due: Notify,
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        Self { due: Notify::new() }
+    }
+
+    pub fn notify(&self) {
+        self.due.notify_one();
+    }
+}
+
+/// Add an event to the outbox, inside the caller's transaction when given one
+pub fn insert<Q: Queryable>(conn: &mut Q, event: &DeviceEvent) -> mysql::Result<()> {
+    let payload = serde_json::to_string(event).expect("events serialize to JSON");
+    conn.exec_drop(
+        "INSERT INTO event_outbox (event_kind, account_id, payload) VALUES (?, ?, ?)",
+        (event.kind.as_str(), event.account_id, payload),
+    )
+}
+
+/// Record an event that does not come with a device write.
+/// While MySQL is unavailable the event is journaled and queued like a heartbeat write.
+pub fn emit(state: &AppState, event: DeviceEvent) {
+    crate::app_with_mysql_and_cache::write_or_queue(state, PendingWrite::Event { event }, Vec::new());
+}
+
+/// Outbox rows sorted by what the relay does with them
+#[derive(Debug, Default, PartialEq)]
+struct Batch {
+    /// Events for the sinks, in id order
+    publish: Vec<(u64, DeviceEvent)>,
+    /// Events inside a maintenance window, marked published without reaching the sinks
+    suppressed: Vec<u64>,
+    /// Rows whose payload does not parse, with the error; they are dead-lettered
+    unreadable: Vec<(u64, String)>,
+}
+
+impl Batch {
+    fn sort(rows: Vec<(u64, String)>, maintenance: &MaintenanceSet) -> Self {
+        let mut batch = Batch::default();
+        for (id, payload) in rows {
+            match serde_json::from_str::<DeviceEvent>(&payload) {
+                Ok(event) => match maintenance.covering(&event) {
+                    Some(window) => {
+                        log::debug!("Outbox event {} ({}) suppressed by maintenance window {}", id, event.kind.as_str(), window.id);
+                        batch.suppressed.push(id);
+                    },
+                    None => batch.publish.push((id, event)),
+                },
+                Err(e) => {
+                    log::error!("Dead-lettering unreadable outbox event {}: {}", id, e);
+                    let mut error = e.to_string();
+                    error.truncate(MAX_ERROR_LEN);
+                    batch.unreadable.push((id, error));
+                },
+            }
+        }
+        batch
+    }
+
+    fn len(&self) -> usize {
+        self.publish.len() + self.suppressed.len() + self.unreadable.len()
+    }
+}
+
+/// Store a batch's outcome through `commit`, and only once that succeeded hand its
+/// events to `publish`, so sinks never see events whose rows were rolled back
+fn relay_rows(
+    rows: Vec<(u64, String)>,
+    maintenance: &MaintenanceSet,
+    commit: impl FnOnce(&Batch) -> Result<()>,
+    mut publish: impl FnMut(DeviceEvent),
+) -> Result<usize> {
+    let batch = Batch::sort(rows, maintenance);
+    commit(&batch)?;
+    let relayed = batch.len();
+    for (_, event) in batch.publish {
+        publish(event);
+    }
+    Ok(relayed)
+}
+
+/// Publish the oldest unpublished events and mark them published in one transaction.
+/// The rows stay locked until commit, so a second hbd instance waits for this batch
+/// instead of relaying later events ahead of it. READ COMMITTED avoids gap locks
+/// that would hold up heartbeat transactions inserting new events meanwhile.
+fn relay_batch(state: &AppState, batch_size: usize) -> Result<usize> {
+    let mut conn = state.get_connection()?;
+    let mut tx = conn.start_transaction(TxOpts::default().set_isolation_level(Some(IsolationLevel::ReadCommitted)))?;
+    let rows: Vec<(u64, String)> = tx.exec(
+        "SELECT id, payload FROM event_outbox WHERE published_at IS NULL AND dead_lettered_at IS NULL
+         ORDER BY id LIMIT ? FOR UPDATE",
+        (batch_size,),
+    )?;
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let mut deliveries = 0;
+    let relayed = relay_rows(rows, &state.maintenance, |batch| {
+        if state.config.webhooks.enabled {
+            for (id, event) in &batch.publish {
+                deliveries += crate::webhooks::enqueue(state, &mut tx, event)
+                    .with_context(|| format!("Failed to queue webhook deliveries for outbox event {}", id))?;
+            }
+        }
+        tx.exec_batch(
+            "UPDATE event_outbox SET published_at = UTC_TIMESTAMP() WHERE id = ?",
+            batch.publish.iter().map(|(id, _)| *id).chain(batch.suppressed.iter().copied()).map(|id| (id,)),
+        )?;
+        tx.exec_batch(
+            "UPDATE event_outbox SET dead_lettered_at = UTC_TIMESTAMP(), error = ? WHERE id = ?",
+            batch.unreadable.iter().map(|(id, error)| (error, id)),
+        )?;
+        tx.commit().context("Failed to mark outbox events published")
+    }, |event| state.events.publish(event))?;
+
+    if deliveries > 0 {
+        state.webhooks.wake();
+    }
+    Ok(relayed)
+}
+
+/// Background task: relay outbox events to the sinks in order, woken by new
+/// events or every poll_interval. A batch that fails is retried from the same
+/// event, so sinks see every event at least once.
+pub async fn run_outbox_relay(state: AppState) {
+    let config = state.config.outbox.clone();
+    let poll_interval = Duration::from_secs(config.poll_interval);
+    loop {
+        tokio::select! {
+            _ = state.outbox.due.notified() => {},
+            _ = tokio::time::sleep(poll_interval) => {},
+        }
+
+        // Keep going while full batches come back, so a backlog drains without waiting
+        loop {
+            let relay_state = state.clone();
+            match tokio::task::spawn_blocking(move || relay_batch(&relay_state, config.batch_size)).await {
+                Ok(Ok(relayed)) => {
+                    if relayed > 0 {
+                        log::debug!("Relayed {} outbox events", relayed);
+                    }
+                    if relayed < config.batch_size {
+                        break;
+                    }
+                },
+                Ok(Err(e)) => {
+                    log::debug!("Unable to relay outbox events: {:#}", e);
+                    break;
+                },
+                Err(e) => {
+                    log::error!("Outbox relay task panicked: {}", e);
+                    break;
+                },
+            }
+        }
+    }
+}
+
+/// Delete published events older than `cutoff` in batches of `batch_size`
+pub fn purge_published(conn: &mut mysql::PooledConn, cutoff: DateTime<Utc>, batch_size: usize) -> Result<u64> {
+    let cutoff = cutoff.format(DB_DATETIME_FORMAT).to_string();
+    let statement = format!(
+        "DELETE FROM event_outbox WHERE published_at IS NOT NULL AND published_at < ? LIMIT {}",
+        batch_size
+    );
+    crate::cleanup::delete_in_batches(batch_size, || {
+        conn.exec_drop(&statement, (&cutoff,)).context("Failed to purge event_outbox")?;
+        Ok(conn.affected_rows())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventKind;
+    use std::cell::RefCell;
+
+    fn rows() -> (Vec<(u64, String)>, DeviceEvent) {
+        let event = DeviceEvent::device(EventKind::Offline, "AA:BB", Some(7), Some(1), None, Utc::now());
+        (vec![(1, serde_json::to_string(&event).unwrap()), (2, "{not json".to_string())], event)
+    }
+
+    #[test]
+    fn test_publishes_only_after_commit() {
+        let (rows, event) = rows();
+        let steps = RefCell::new(Vec::new());
+        let relayed = relay_rows(rows, &MaintenanceSet::new(), |batch| {
+            assert_eq!(batch.publish, vec![(1, event.clone())]);
+            steps.borrow_mut().push("commit".to_string());
+            Ok(())
+        }, |published| steps.borrow_mut().push(format!("publish {}", published.kind.as_str())));
+        assert_eq!(relayed.unwrap(), 2);
+        assert_eq!(*steps.borrow(), vec!["commit".to_string(), "publish offline".to_string()]);
+
+        // A failed commit publishes nothing; the rows are relayed again on the next pass
+        let (rows, _) = self::rows();
+        let mut published = 0;
+        let failed = relay_rows(rows, &MaintenanceSet::new(), |_| anyhow::bail!("deadlock"), |_| published += 1);
+        assert!(failed.is_err());
+        assert_eq!(published, 0);
+    }
+
+    #[test]
+    fn test_unreadable_rows_are_dead_lettered() {
+        let (rows, _) = rows();
+        let batch = Batch::sort(rows, &MaintenanceSet::new());
+        assert_eq!(batch.publish.len(), 1);
+        assert!(batch.suppressed.is_empty());
+        assert_eq!(batch.unreadable.len(), 1);
+        assert_eq!(batch.unreadable[0].0, 2);
+        assert!(!batch.unreadable[0].1.is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{Context, Result};
+use mysql::prelude::*;
+use serde::Serialize;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// A connection for read-only queries, from a replica or the primary
+pub struct ReadConnection {
+    pub conn: mysql::PooledConn,
+    /// Replica endpoint the connection came from, None for the primary
+    pub replica: Option<String>,
+}
+
+impl Deref for ReadConnection {
+    type Target = mysql::PooledConn;
+
+    fn deref(&self) -> &Self::Target {
+        &self.conn
+    }
+}
+
+impl DerefMut for ReadConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.conn
+    }
+}
+
+/// Health of a single replica, as reported by /health
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicaStatus {
+    pub endpoint: String,
+    pub healthy: bool,
+}
+
+struct Replica {
+    endpoint: String,
+    opts: mysql::Opts,
+    pool: RwLock<Option<mysql::Pool>>,
+    healthy: AtomicBool,
+}
+
+impl Replica {
+    fn pool(&self) -> Option<mysql::Pool> {
+        self.pool.read().unwrap().clone()
+    }
+
+    /// Ping the replica, creating its pool on first success
+    fn check(&self) -> Result<()> {
+        let pool = match self.pool() {
+            Some(pool) => pool,
+            None => {
+                let pool = mysql::Pool::new(self.opts.clone())
+                    .with_context(|| format!("Failed to create pool for replica {}", self.endpoint))?;
+                *self.pool.write().unwrap() = Some(pool.clone());
+                pool
+            }
+        };
+        pool.get_conn()?.query_drop("SELECT 1")?;
+        Ok(())
+    }
+}
+
+/// Read replicas used for authorization lookups and admin reads.
+/// Replicas are picked round-robin among those passing health checks;
+/// callers fall back to the primary when none are healthy.
+pub struct ReplicaSet {
+    replicas: Vec<Replica>,
+    next: AtomicUsize,
+}
+
+impl ReplicaSet {
+    pub fn new(config: &Config) -> Self {
+        let replicas = config.database.replicas.iter().map(|replica| Replica {
+            endpoint: format!("{}:{}", replica.host, replica.port),
+            opts: config.replica_mysql_opts(replica).into(),
+            pool: RwLock::new(None),
+            healthy: AtomicBool::new(false),
+        }).collect();
+        Self { replicas, next: AtomicUsize::new(0) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replicas.is_empty()
+    }
+
+    /// Healthy replicas in round-robin order, starting one further on each call
+    fn rotation(&self) -> impl Iterator<Item = &Replica> {
+        let count = self.replicas.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..count)
+            .map(move |offset| &self.replicas[(start + offset) % count])
+            .filter(|replica| replica.healthy.load(Ordering::Relaxed))
+    }
+
+    /// Get a connection from the next healthy replica, if any
+    pub fn get_conn(&self) -> Option<ReadConnection> {
+        for replica in self.rotation() {
+            let Some(pool) = replica.pool() else { continue };
+            match pool.get_conn() {
+                Ok(conn) => return Some(ReadConnection { conn, replica: Some(replica.endpoint.clone()) }),
+                Err(e) => {
+                    log::warn!("Replica {} unavailable, marking unhealthy: {}", replica.endpoint, e);
+                    replica.healthy.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+        None
+    }
+
+    /// Take a replica out of rotation until its next successful health check
+    pub fn mark_unhealthy(&self, endpoint: &str) {
+        if let Some(replica) = self.replicas.iter().find(|r| r.endpoint == endpoint) {
+            replica.healthy.store(false, Ordering::Relaxed);
+        }
+    }
+
+    pub fn status(&self) -> Vec<ReplicaStatus> {
+        self.replicas.iter().map(|replica| ReplicaStatus {
+            endpoint: replica.endpoint.clone(),
+            healthy: replica.healthy.load(Ordering::Relaxed),
+        }).collect()
+    }
+
+    fn check_all(&self) {
+        for replica in &self.replicas {
+            let was_healthy = replica.healthy.load(Ordering::Relaxed);
+            match replica.check() {
+                Ok(()) => {
+                    if !was_healthy {
+                        log::info!("Replica {} is healthy, routing reads to it", replica.endpoint);
+                    }
+                    replica.healthy.store(true, Ordering::Relaxed);
+                },
+                Err(e) => {
+                    if was_healthy {
+                        log::warn!("Replica {} failed health check: {:#}", replica.endpoint, e);
+                    }
+                    replica.healthy.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Background task: periodically health check every replica
+    pub async fn run_health_checks(self: Arc<Self>, interval: Duration) {
+        loop {
+            let replicas = self.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || replicas.check_all()).await {
+                log::error!("Replica health check task panicked: {}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ReplicaConfig;
+
+    fn replica_set(ports: &[u16]) -> ReplicaSet {
+        let mut config = Config::default();
+        config.database.replicas = ports.iter().map(|port| ReplicaConfig {
+            host: "127.0.0.1".to_string(),
+            port: *port,
+            user: None,
+            password: None,
+        }).collect();
+        ReplicaSet::new(&config)
+    }
+
+    fn endpoints(set: &ReplicaSet) -> Vec<&str> {
+        set.rotation().map(|replica| replica.endpoint.as_str()).collect()
+    }
+
+    #[test]
+    fn test_round_robin_over_healthy_replicas() {
+        let set = replica_set(&[1, 2, 3]);
+        // Replicas stay out of rotation until a health check passes
+        assert!(endpoints(&set).is_empty());
+
+        for replica in &set.replicas {
+            replica.healthy.store(true, Ordering::Relaxed);
+        }
+        set.mark_unhealthy("127.0.0.1:2");
+        assert_eq!(endpoints(&set), vec!["127.0.0.1:3", "127.0.0.1:1"]);
+        assert_eq!(endpoints(&set), vec!["127.0.0.1:3", "127.0.0.1:1"]);
+        assert_eq!(endpoints(&set), vec!["127.0.0.1:1", "127.0.0.1:3"]);
+        assert_eq!(set.status().iter().filter(|status| status.healthy).count(), 2);
+    }
+
+    #[test]
+    fn test_failed_health_check_fails_over() {
+        // Nothing listens on port 1, so every check fails
+        let set = replica_set(&[1]);
+        set.replicas[0].healthy.store(true, Ordering::Relaxed);
+        set.check_all();
+        assert!(!set.status()[0].healthy);
+        assert!(set.get_conn().is_none());
+    }
+}
@@ -0,0 +1,312 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDateTime, Utc};
+use mysql::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::history::Resolution;
+use crate::server::AppState;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A span during which a device was heartbeating: (first_seen, last_seen)
+pub type ActivityPeriod = (DateTime<Utc>, DateTime<Utc>);
+
+/// Uptime figures for one device over a reporting window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UptimeStats {
+    pub uptime_percent: f64,
+    pub online_seconds: i64,
+    pub outage_count: u32,
+    pub longest_outage_seconds: i64,
+}
+
+/// Compute uptime from heartbeat activity periods.
+/// A device counts as online from each (first_seen, last_seen) period until
+/// `offline_after` past its last heartbeat; anything else in [from, to) is an outage.
+pub fn compute_uptime(
+    periods: &[ActivityPeriod],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    offline_after: ChronoDuration,
+) -> UptimeStats {
+    let mut periods: Vec<_> = periods.iter()
+        .map(|(first, last)| ((*first).max(from), (*last + offline_after).min(to)))
+        .filter(|(start, end)| start < end)
+        .collect();
+    periods.sort();
+
+    let mut online = ChronoDuration::zero();
+    let mut outage_count = 0;
+    let mut longest_outage = ChronoDuration::zero();
+    let mut cursor = from;
+    let mut record_gap = |gap: ChronoDuration| {
+        if gap > ChronoDuration::zero() {
+            outage_count += 1;
+            longest_outage = longest_outage.max(gap);
+        }
+    };
+
+    for (start, end) in periods {
+        if start > cursor {
+            record_gap(start - cursor);
+            cursor = start;
+        }
+        if end > cursor {
+            online += end - cursor;
+            cursor = end;
+        }
+    }
+    record_gap(to - cursor);
+
+    let window = (to - from).num_seconds().max(1);
+    UptimeStats {
+        uptime_percent: (online.num_seconds() as f64 * 10000.0 / window as f64).round() / 100.0,
+        online_seconds: online.num_seconds(),
+        outage_count,
+        longest_outage_seconds: longest_outage.num_seconds(),
+    }
+}
+
+/// Which devices a report covers
+#[derive(Debug, Clone)]
+pub enum ReportScope {
+    Device(String),
+    Zone(i32),
+    Account(i32),
+}
+
+impl ReportScope {
+    /// Condition on `devices` (columns prefixed with `alias`) selecting the devices in scope,
+    /// further limited to `account` for account-scoped callers
+    fn device_filter(&self, alias: &str, account: Option<i32>) -> (String, Vec<mysql::Value>) {
+        let (column, value): (&str, mysql::Value) = match self {
+            ReportScope::Device(mac) => ("mac_address = UPPER(?)", mac.clone().into()),
+            ReportScope::Zone(zone) => ("zone_number = ?", (*zone).into()),
+            ReportScope::Account(account) => ("account_id = ?", (*account).into()),
+        };
+        let mut filter = format!("{}{}", alias, column);
+        let mut values = vec![value];
+        if let Some(account) = account {
+            filter.push_str(&format!(" AND {}account_id = ?", alias));
+            values.push(account.into());
+        }
+        (filter, values)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceUptime {
+    pub mac_address: String,
+    #[serde(flatten)]
+    pub stats: UptimeStats,
+}
+
+fn parse_db_datetime(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, DB_DATETIME_FORMAT).ok().map(|dt| dt.and_utc())
+}
+
+/// Compute per-device uptime for every device in scope, limited to `account` if given
+pub fn device_uptimes(
+    conn: &mut mysql::PooledConn,
+    scope: &ReportScope,
+    account: Option<i32>,
+    resolution: Resolution,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    offline_after: ChronoDuration,
+) -> mysql::Result<Vec<DeviceUptime>> {
+    let (filter, values) = scope.device_filter("", account);
+    let macs: Vec<String> = conn.exec(
+        format!("SELECT UPPER(mac_address) FROM devices WHERE {} ORDER BY mac_address", filter),
+        values,
+    )?;
+
+    let (filter, mut values) = scope.device_filter("d.", account);
+    values.push((from - offline_after - resolution.duration()).format(DB_DATETIME_FORMAT).to_string().into());
+    values.push(to.format(DB_DATETIME_FORMAT).to_string().into());
+
+    // Look back far enough that a heartbeat just before `from` still counts
+    let rows: Vec<(String, String, String)> = conn.exec(
+        format!(
+            "SELECT h.mac_address, DATE_FORMAT(h.first_seen, '%Y-%m-%d %H:%i:%s'), DATE_FORMAT(h.last_seen, '%Y-%m-%d %H:%i:%s')
+             FROM {} h
+             JOIN devices d ON d.mac_address = h.mac_address
+             WHERE {} AND h.bucket_start >= ? AND h.bucket_start < ?",
+            resolution.table(), filter
+        ),
+        values,
+    )?;
+
+    let mut periods: BTreeMap<String, Vec<ActivityPeriod>> =
+        macs.into_iter().map(|mac| (mac, Vec::new())).collect();
+    for (mac, first_seen, last_seen) in rows {
+        if let (Some(first), Some(last)) = (parse_db_datetime(&first_seen), parse_db_datetime(&last_seen)) {
+            periods.entry(mac.to_uppercase()).or_default().push((first, last));
+        }
+    }
+
+    Ok(periods.into_iter().map(|(mac_address, periods)| DeviceUptime {
+        stats: compute_uptime(&periods, from, to, offline_after),
+        mac_address,
+    }).collect())
+}
+
+/// Aggregate across devices: mean uptime, total outages, longest single outage
+pub fn summarize(devices: &[DeviceUptime]) -> UptimeStats {
+    let count = devices.len().max(1) as f64;
+    UptimeStats {
+        uptime_percent: (devices.iter().map(|d| d.stats.uptime_percent).sum::<f64>() / count * 100.0).round() / 100.0,
+        online_seconds: devices.iter().map(|d| d.stats.online_seconds).sum(),
+        outage_count: devices.iter().map(|d| d.stats.outage_count).sum(),
+        longest_outage_seconds: devices.iter().map(|d| d.stats.longest_outage_seconds).max().unwrap_or(0),
+    }
+}
+
+/// Render device rows as CSV
+pub fn to_csv(devices: &[DeviceUptime]) -> String {
+    let mut csv = String::from("mac_address,uptime_percent,online_seconds,outage_count,longest_outage_seconds\n");
+    for device in devices {
+        csv.push_str(&format!(
+            "{},{:.2},{},{},{}\n",
+            device.mac_address,
+            device.stats.uptime_percent,
+            device.stats.online_seconds,
+            device.stats.outage_count,
+            device.stats.longest_outage_seconds
+        ));
+    }
+    csv
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UptimeQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub resolution: Option<Resolution>,
+    pub format: Option<ReportFormat>,
+}
+
+async fn uptime_report(
+    state: AppState,
+    principal: Principal,
+    scope: ReportScope,
+    params: UptimeQuery,
+) -> Result<Response, ApiError> {
+    if let ReportScope::Account(account) = scope
+        && !principal.can_access(Some(account))
+    {
+        return Err(ApiError::forbidden());
+    }
+    let to = params.to.unwrap_or_else(Utc::now);
+    let from = params.from.unwrap_or(to - ChronoDuration::days(7));
+    if from >= to {
+        return Err(ApiError::bad_request("from must be before to"));
+    }
+    let resolution = params.resolution.unwrap_or(Resolution::Minute);
+    let offline_after = ChronoDuration::seconds(state.config.history.offline_after as i64);
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let devices = device_uptimes(&mut conn, &scope, principal.account_id, resolution, from, to, offline_after)
+        .map_err(|e| ApiError::database("Failed to compute uptime report", e))?;
+    if devices.is_empty() {
+        return Err(ApiError::not_found(match scope {
+            ReportScope::Device(mac) => format!("Device {} not found", mac),
+            ReportScope::Zone(zone) => format!("No devices in zone {}", zone),
+            ReportScope::Account(account) => format!("No devices in account {}", account),
+        }));
+    }
+
+    match params.format.unwrap_or_default() {
+        ReportFormat::Csv => Ok(([(header::CONTENT_TYPE, "text/csv")], to_csv(&devices)).into_response()),
+        ReportFormat::Json => Ok(Json(serde_json::json!({
+            "from": from.to_rfc3339(),
+            "to": to.to_rfc3339(),
+            "resolution": resolution,
+            "summary": summarize(&devices),
+            "devices": devices
+        })).into_response()),
+    }
+}
+
+/// Uptime report for a single device
+pub async fn get_device_uptime(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(mac): Path<String>,
+    Query(params): Query<UptimeQuery>,
+) -> Result<Response, ApiError> {
+    uptime_report(state, principal, ReportScope::Device(mac), params).await
+}
+
+/// Uptime report for every device in a zone
+pub async fn get_zone_uptime(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(zone): Path<i32>,
+    Query(params): Query<UptimeQuery>,
+) -> Result<Response, ApiError> {
+    uptime_report(state, principal, ReportScope::Zone(zone), params).await
+}
+
+/// Uptime report for every device in an account
+pub async fn get_account_uptime(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(account): Path<i32>,
+    Query(params): Query<UptimeQuery>,
+) -> Result<Response, ApiError> {
+    uptime_report(state, principal, ReportScope::Account(account), params).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_full_uptime() {
+        let periods = vec![(at(9, 55), at(11, 0))];
+        let stats = compute_uptime(&periods, at(10, 0), at(11, 0), ChronoDuration::minutes(10));
+        assert_eq!(stats.uptime_percent, 100.0);
+        assert_eq!(stats.outage_count, 0);
+    }
+
+    #[test]
+    fn test_outages_are_counted() {
+        // Online 10:00-10:20 (+5m grace), gap, online 10:40-10:50 (+5m), then silent
+        let periods = vec![(at(10, 0), at(10, 20)), (at(10, 40), at(10, 50))];
+        let stats = compute_uptime(&periods, at(10, 0), at(11, 0), ChronoDuration::minutes(5));
+        assert_eq!(stats.online_seconds, 40 * 60);
+        assert_eq!(stats.outage_count, 2);
+        assert_eq!(stats.longest_outage_seconds, 15 * 60);
+        assert_eq!(stats.uptime_percent, 66.67);
+    }
+
+    #[test]
+    fn test_no_heartbeats_is_one_outage() {
+        let stats = compute_uptime(&[], at(10, 0), at(11, 0), ChronoDuration::minutes(5));
+        assert_eq!(stats.uptime_percent, 0.0);
+        assert_eq!(stats.outage_count, 1);
+        assert_eq!(stats.longest_outage_seconds, 3600);
+    }
+}
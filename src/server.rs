@@ -3,7 +3,7 @@ use axum::{
     http::{HeaderMap,StatusCode},
     response::Json,
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
 use mysql::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -12,19 +12,48 @@ use std::future::Future;
 use std::pin::Pin;
 use anyhow::{Result, Context};
 use std::net::SocketAddr;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::accounts::AccountRegistry;
+use crate::auth::Principal;
+use crate::circuit_breaker::{BreakerState, CircuitBreaker};
+use crate::journal::Journal;
+use crate::replicas::{ReadConnection, ReplicaSet};
+use crate::squelch::SquelchSet;
+use crate::webhooks::WebhookRegistry;
+use crate::outbox::Outbox;
+use crate::broker::BrokerBuffer;
+use crate::stream::StreamHub;
+use crate::maintenance::MaintenanceSet;
+use crate::firmware::CampaignSet;
+use crate::intervals::{LoadMonitor, SlotTracker};
+use crate::telemetry::Telemetry;
+use crate::history::HeartbeatHistory;
+use crate::error::ApiError;
+use crate::events::EventBus;
+use crate::metrics::AccountMetrics;
+use crate::write_queue::WriteQueue;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     pub id: Option<u32>,
     pub name: String,
     pub email: String,
+    /// Account the user acts on, None for fleet-wide operators
+    pub account_id: Option<i32>,
     pub created_at: Option<String>,
+    /// Bearer token for the admin APIs, only returned when it is issued
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateUserRequest {
     pub name: String,
     pub email: String,
+    #[serde(default)]
+    pub account_id: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -76,7 +105,7 @@ pub struct StoredProcResponse {
     pub device: Option<DeviceInfo>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 pub struct DeviceInfo {
     pub id: u32,
     pub mac_address: String,
@@ -85,49 +114,217 @@ pub struct DeviceInfo {
     pub last_heartbeat: Option<String>,
     pub camera_number: Option<i32>,
     pub zone_number: Option<i32>,
+    pub created_at: Option<String>,
+    pub last_modified: Option<String>,
+    /// Owning account, None for devices not assigned to one
+    pub account_id: Option<i32>,
+    /// False once deactivated through the device registry
+    pub active: bool,
+    /// Heartbeat seen within history.offline_after, or two of the device's intervals if longer
+    pub online: bool,
+    /// Bouncing between online and offline past alerts.flap_threshold
+    pub flapping: bool,
+    /// Expected heartbeat interval in seconds set for this device; None uses its zone's or account's
+    pub heartbeat_interval: Option<u32>,
+    /// Interval last returned to the device, None until it reports
+    pub interval: Option<u64>,
+    /// last_heartbeat as an instant; the string above is in the MySQL session time zone
+    #[serde(skip)]
+    pub last_heartbeat_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+/// Events buffered per subscriber before the slowest one starts losing them
+const EVENT_BUFFER: usize = 1024;
+
 #[derive(Clone)]
 pub struct AppState {
-    pub db_pool: mysql::Pool,
+    pub config: Arc<crate::config::Config>,
+    /// None until MySQL has been reached at least once
+    pub db_pool: Arc<RwLock<Option<mysql::Pool>>>,
     pub heart_beat_cache: crate::cache::HeartbeatCache<'static>,
+    pub db_breaker: Arc<CircuitBreaker>,
+    /// Writes waiting for MySQL while the breaker is open
+    pub write_queue: Arc<WriteQueue>,
+    /// Durable copy of the write queue, None when disabled
+    pub journal: Option<Arc<Journal>>,
+    /// Read replicas for lookup queries
+    pub replicas: Arc<ReplicaSet>,
+    /// Heartbeat arrivals waiting to be flushed to the history tables
+    pub history: Arc<HeartbeatHistory>,
+    /// Per-account settings and heartbeat rate limits
+    pub accounts: Arc<AccountRegistry>,
+    /// Heartbeat counters per account
+    pub metrics: Arc<AccountMetrics>,
+    /// Pending squelches set through the API
+    pub squelches: Arc<SquelchSet>,
+    /// Device and site events for the configured sinks
+    pub events: Arc<EventBus>,
+    /// Registered webhooks
+    pub webhooks: Arc<WebhookRegistry>,
+    /// Wakes the relay for events written to the outbox
+    pub outbox: Arc<Outbox>,
+    /// Events waiting to be published to NATS
+    pub broker: Arc<BrokerBuffer>,
+    /// Live heartbeats for WebSocket clients
+    pub stream: Arc<StreamHub>,
+    /// Maintenance windows that keep events from the sinks
+    pub maintenance: Arc<MaintenanceSet>,
+    /// Overall heartbeat rate, for lengthening intervals under load
+    pub load: Arc<LoadMonitor>,
+    /// How evenly heartbeats arrive across their intervals
+    pub slots: Arc<SlotTracker>,
+    /// Active firmware campaigns and the devices they wait on
+    pub firmware: Arc<CampaignSet>,
 }
 
 impl AppState {
     pub fn new() -> anyhow::Result<Self> {
         let config = crate::config::Config::load().unwrap_or_default();
+        let breaker_config = &config.database.circuit_breaker;
+        let open_duration = std::time::Duration::from_secs(breaker_config.open_duration);
         
-        // Create connection pool using the enhanced config
-        let db_pool = config.create_connection_pool()
-            .context("Failed to create database connection pool")?;
+        // Start not-ready; connect_at_startup creates the pool once the server is listening
+        let db_pool: Option<mysql::Pool> = None;
+        let db_breaker = CircuitBreaker::new_open(breaker_config.failure_threshold, open_duration);
+
+        let replicas = ReplicaSet::new(&config);
+        let history = HeartbeatHistory::new(config.history.max_pending_buckets);
 
         // Initialize the cache
         let heart_beat_cache = crate::cache::HeartbeatCache::new();
+        let write_queue = WriteQueue::new(breaker_config.max_queued_writes);
+
+        // Reload writes that were still pending when hbd last stopped
+        let journal_config = &config.database.journal;
+        let journal = if journal_config.enabled {
+            let (journal, pending) = Journal::open(
+                &journal_config.directory,
+                journal_config.segment_size,
+                journal_config.sync_writes,
+            ).context("Failed to open write journal")?;
+            for record in pending {
+                write_queue.push(record.write, Some(record.seq));
+            }
+            Some(Arc::new(journal))
+        } else {
+            None
+        };
 
         log::info!("Application state initialized with connection pool and cache");
 
+        let broker_buffer_size = config.broker.buffer_size;
+        let stream_buffer_size = config.stream.buffer_size;
         Ok(AppState { 
-            db_pool,
+            config: Arc::new(config),
+            db_pool: Arc::new(RwLock::new(db_pool)),
             heart_beat_cache,
+            db_breaker: Arc::new(db_breaker),
+            write_queue: Arc::new(write_queue),
+            journal,
+            replicas: Arc::new(replicas),
+            history: Arc::new(history),
+            accounts: Arc::new(AccountRegistry::new()),
+            metrics: Arc::new(AccountMetrics::new()),
+            squelches: Arc::new(SquelchSet::new()),
+            events: Arc::new(EventBus::new(EVENT_BUFFER)),
+            webhooks: Arc::new(WebhookRegistry::new()),
+            outbox: Arc::new(Outbox::new()),
+            broker: Arc::new(BrokerBuffer::new(broker_buffer_size)),
+            stream: Arc::new(StreamHub::new(stream_buffer_size)),
+            maintenance: Arc::new(MaintenanceSet::new()),
+            load: Arc::new(LoadMonitor::new()),
+            slots: Arc::new(SlotTracker::new()),
+            firmware: Arc::new(CampaignSet::new()),
         })
     }
 
     /// Get a connection from the pool
     /// This is much more efficient than creating new connections
     pub fn get_connection(&self) -> anyhow::Result<mysql::PooledConn> {
-        self.db_pool.get_conn()
-            .context("Failed to get connection from pool")
+        if !self.db_breaker.allow_request() {
+            anyhow::bail!("Database circuit breaker is open");
+        }
+        let pool = self.database_pool()
+            .context("Database connection pool is not initialized")?;
+        // Success is recorded by callers once a statement has run; a checkout proves little
+        match pool.get_conn() {
+            Ok(conn) => Ok(conn),
+            Err(e) => {
+                self.db_breaker.record_failure();
+                Err(e).context("Failed to get connection from pool")
+            }
+        }
+    }
+
+    /// Get a connection for read-only queries.
+    /// Uses a healthy replica when configured, otherwise falls back to the primary.
+    pub fn get_read_connection(&self) -> anyhow::Result<ReadConnection> {
+        if let Some(conn) = self.replicas.get_conn() {
+            return Ok(conn);
+        }
+        self.get_connection().map(|conn| ReadConnection { conn, replica: None })
+    }
+
+    /// Current connection pool, if one has been established
+    pub fn database_pool(&self) -> Option<mysql::Pool> {
+        self.db_pool.read().unwrap().clone()
+    }
+
+    /// Install a connection pool created after startup
+    pub fn set_database_pool(&self, pool: mysql::Pool) {
+        let pool = Self::prepare_pool(&self.config, pool);
+        *self.db_pool.write().unwrap() = Some(pool);
+    }
+
+    /// Bring the schema up to date on a newly created pool
+    fn prepare_pool(config: &crate::config::Config, pool: mysql::Pool) -> mysql::Pool {
+        if config.database.run_migrations
+            && let Err(e) = crate::migrations::run(&pool)
+        {
+            log::error!("Schema migrations failed: {:#}", e);
+        }
+        pool
+    }
+
+    /// True once a connection pool exists
+    pub fn is_ready(&self) -> bool {
+        self.db_pool.read().unwrap().is_some()
+    }
+
+    /// True while running in cache-only degraded mode
+    pub fn is_degraded(&self) -> bool {
+        self.db_breaker.is_open()
     }
 }
 
 // API Handlers
 
 /// Health check endpoint
-pub async fn health() -> Result<Json<serde_json::Value>, StatusCode> {
+/// Reports "degraded" while MySQL is unreachable and heartbeats are served from the cache
+pub async fn health(State(state): State<AppState>) -> Result<Json<serde_json::Value>, StatusCode> {
+    let database = state.db_breaker.state();
+    let status = if database == BreakerState::Closed { "healthy" } else { "degraded" };
     Ok(Json(serde_json::json!({
-        "status": "healthy",
+        "status": status,
         "timestamp": chrono::Utc::now().to_rfc3339(),
-        "service": "mysql_connection_demo"
+        "service": "mysql_connection_demo",
+        "ready": state.is_ready(),
+        "database": database,
+        "replicas": state.replicas.status(),
+        "queued_writes": state.write_queue.len(),
+        "pending_history_buckets": state.history.pending(),
+        "journaled_writes": state.journal.as_ref().map(|j| j.pending_count())
+    })))
+}
+
+/// Readiness endpoint for orchestrators: 503 until the MySQL pool is available
+pub async fn ready(State(state): State<AppState>) -> Result<Json<serde_json::Value>, StatusCode> {
+    if !state.is_ready() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    Ok(Json(serde_json::json!({
+        "status": "ready",
+        "timestamp": chrono::Utc::now().to_rfc3339()
     })))
 }
 
@@ -137,7 +334,7 @@ pub async fn get_db_info(
     State(state): State<AppState>
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     log::info!("eddie: headers{:?}", headers);
-    match state.get_connection() {
+    match state.get_read_connection() {
         Ok(mut conn) => {
             let version: Vec<String> = conn.query("SELECT VERSION()")
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -147,7 +344,8 @@ pub async fn get_db_info(
             Ok(Json(serde_json::json!({
                 "mysql_version": version.first().unwrap_or(&"Unknown".to_string()),
                 "databases": databases,
-                "connection_status": "connected"
+                "connection_status": "connected",
+                "replica": conn.replica
             })))
         },
         Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE)
@@ -155,12 +353,24 @@ pub async fn get_db_info(
 }
 
 
+/// Telemetry sent with a heartbeat as query parameters and/or a JSON body; the body wins.
+/// Telemetry that does not parse is left out instead of refusing the heartbeat.
+fn heartbeat_telemetry(query: Option<Query<HashMap<String, String>>>, body: Option<Json<Telemetry>>) -> Telemetry {
+    let query = query.map(|Query(params)| Telemetry::from_query(&params)).unwrap_or_default();
+    match body {
+        Some(Json(body)) => query.merge(body),
+        None => query,
+    }
+}
+
 /// Handle device heartbeat with mission-critical write-through caching
 pub async fn handle_heartbeat(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     State(state): State<AppState>,
-    Query(params): Query<HeartbeatQuery>
+    Query(params): Query<HeartbeatQuery>,
+    telemetry: Option<Query<HashMap<String, String>>>,
+    body: Option<Json<Telemetry>>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     log::info!("eddie: headers{:?}", headers);
     log::info!("eddie: addr{:?}", addr);
@@ -169,6 +379,7 @@ pub async fn handle_heartbeat(
     crate::app_with_mysql_and_cache::handle_heartbeat_with_cache(
         state.clone(),
         params,
+        heartbeat_telemetry(telemetry, body),
         &state.heart_beat_cache,
         false
     ).await
@@ -177,7 +388,9 @@ pub async fn handle_heartbeat(
 pub async fn handle_heartbeat_uninitialized(
     headers: HeaderMap,
     State(state): State<AppState>,
-    Query(params): Query<HeartbeatQuery>
+    Query(params): Query<HeartbeatQuery>,
+    telemetry: Option<Query<HashMap<String, String>>>,
+    body: Option<Json<Telemetry>>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     log::info!("eddie: headers{:?}", headers);
     
@@ -185,110 +398,131 @@ pub async fn handle_heartbeat_uninitialized(
     crate::app_with_mysql_and_cache::handle_heartbeat_with_cache(
         state.clone(),
         params,
+        heartbeat_telemetry(telemetry, body),
         &state.heart_beat_cache,
         true
     ).await
 }
 
-/// Direct stored procedure endpoint for testing
+/// Stored procedure test endpoint (admin only).
+/// Registers the device if needed, runs set_device_last_heartbeat and returns the updated device.
 pub async fn call_stored_procedure(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
     Json(payload): Json<StoredProcRequest>
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let mut conn = state.get_connection().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<StoredProcResponse>, ApiError> {
+    principal.require_global()?;
+    if payload.mac_address.trim().is_empty() {
+        return Err(ApiError::bad_request("mac_address is required"));
+    }
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
     
     // First ensure device exists (the stored procedure requires existing device)
     let device_exists: Vec<u32> = conn.exec(
         "SELECT id FROM devices WHERE mac_address = UPPER(?)",
         (&payload.mac_address,)
-    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    ).map_err(|e| ApiError::database("Failed to look up device", e))?;
     
     if device_exists.is_empty() {
         // Insert new device first
         conn.exec_drop(
             "INSERT INTO devices (mac_address, local_ip_address, global_ip_address, last_heartbeat, camera_number, zone_number) VALUES (UPPER(?), ?, ?, NOW(), ?, ?)",
             (&payload.mac_address, &payload.private_ip_address, &payload.public_ip_address, payload.camera_number.unwrap_or(1), payload.zone_number.unwrap_or(1))
-        ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        ).map_err(|e| ApiError::database("Failed to register device", e))?;
     }
     
     // Call the stored procedure
-    let result: Result<Vec<(u32, String, Option<String>, String)>, mysql::Error> = conn.exec(
+    conn.exec_drop(
         "CALL set_device_last_heartbeat(?, ?, ?, @msg, @prev_ip)",
         (&payload.mac_address, &payload.private_ip_address, &payload.public_ip_address)
-    );
-    
-    match result {
-        Ok(rows) => {
-            // Get the output parameters
-            let output_params: Vec<(Option<String>, Option<String>)> = conn.exec(
-                "SELECT @msg as message, @prev_ip as previous_ip",
-                ()
-            ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            
-            let (message, previous_ip) = output_params.first()
-                .map(|(msg, prev)| (msg.clone().unwrap_or("OK".to_string()), prev.clone()))
-                .unwrap_or(("OK".to_string(), None));
-            
-            // Get the procedure result
-            let (device_id, proc_message, prev_ip_result, timestamp) = rows.first()
-                .map(|(id, msg, prev, ts)| (*id, msg.clone(), prev.clone(), ts.clone()))
-                .unwrap_or((0, "Unknown".to_string(), None, "Unknown".to_string()));
-            
-            // Get the updated device info
-            let device_info: Vec<(u32, String, Option<String>, Option<String>, Option<String>, String, String, Option<i32>, Option<i32>)> = conn.exec(
-                "SELECT id, mac_address, local_ip_address, global_ip_address, last_heartbeat, created_at, last_modified, camera_number, zone_number 
-                 FROM devices WHERE mac_address = UPPER(?)",
-                (&payload.mac_address,)
-            ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            
-            if let Some((id, mac_addr, local_ip, global_ip, last_hb, created, modified, camera, zone)) = device_info.first() {
-                Ok(Json(serde_json::json!({
-                    "status": "success",
-                    "method": "stored_procedure",
-                    "message": message,
-                    "previous_private_ip": previous_ip,
-                    "procedure_result": {
-                        "device_id": device_id,
-                        "message": proc_message,
-                        "previous_ip_from_procedure": prev_ip_result,
-                        "timestamp": timestamp
-                    },
-                    "device": {
-                        "id": id,
-                        "mac_address": mac_addr,
-                        "local_ip_address": local_ip,
-                        "global_ip_address": global_ip,
-                        "last_heartbeat": last_hb,
-                        "created_at": created,
-                        "last_modified": modified,
-                        "camera_number": camera,
-                        "zone_number": zone
-                    }
-                })))
-            } else {
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
-            }
+    ).map_err(|e| match e {
+        mysql::Error::MySqlError(ref err) => {
+            log::error!("Stored procedure error: {}", err);
+            ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "stored_procedure_failed", err.message.clone())
         },
-        Err(e) => {
-            eprintln!("Stored procedure error: {}", e);
-            Ok(Json(serde_json::json!({
-                "status": "error",
-                "method": "stored_procedure",
-                "error": e.to_string()
-            })))
-        }
-    }
+        e => ApiError::database("Stored procedure call failed", e),
+    })?;
+    
+    // Get the output parameters
+    let (message, previous_private_ip): (Option<String>, Option<String>) = conn
+        .query_first("SELECT @msg, @prev_ip")
+        .map_err(|e| ApiError::database("Failed to read procedure output", e))?
+        .unwrap_or((None, None));
+    
+    // The procedure may have changed the device's addresses
+    crate::devices::invalidate_cache(&state, &payload.mac_address);
+    
+    // Get the updated device info
+    let device = crate::devices::find_device(&mut conn, &payload.mac_address, state.config.history.offline_after)
+        .map_err(|e| ApiError::database("Failed to read updated device", e))?
+        .ok_or_else(|| ApiError::not_found(format!("Device {} not found after update", payload.mac_address)))?;
+    
+    Ok(Json(StoredProcResponse {
+        status: "success".to_string(),
+        method: "stored_procedure".to_string(),
+        message: message.unwrap_or_else(|| "OK".to_string()),
+        previous_private_ip,
+        device: Some(device),
+    }))
 }
 
 
+/// Routes that require an admin or account bearer token.
+/// Account tokens only see their own account's devices, history and reports.
+fn admin_router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/heartbeat/procedure", post(call_stored_procedure))
+        .route("/api/devices", get(crate::devices::list_devices).post(crate::devices::create_device))
+        .route("/api/devices/:mac", get(crate::devices::get_device)
+            .put(crate::devices::update_device)
+            .delete(crate::devices::deactivate_device))
+        .route("/api/users", get(crate::users::list_users).post(crate::users::create_user))
+        .route("/api/users/:id", get(crate::users::get_user).delete(crate::users::delete_user))
+        .route("/api/users/:id/token", post(crate::users::rotate_token))
+        .route("/api/accounts/:account/settings", get(crate::accounts::get_account_settings)
+            .put(crate::accounts::update_account_settings))
+        .route("/api/squelches", get(crate::squelch::list_squelches))
+        .route("/api/devices/:mac/telemetry", get(crate::telemetry::get_device_telemetry))
+        .route("/api/devices/:mac/squelch", post(crate::squelch::squelch_device)
+            .delete(crate::squelch::unsquelch_device))
+        .route("/api/topology", get(crate::zones::get_topology))
+        .route("/api/zones", post(crate::zones::create_zone))
+        .route("/api/zones/:zone", get(crate::zones::get_zone)
+            .put(crate::zones::update_zone)
+            .delete(crate::zones::delete_zone))
+        .route("/api/zones/:zone/squelch", post(crate::squelch::squelch_zone)
+            .delete(crate::squelch::unsquelch_zone))
+        .route("/api/accounts/:account/squelch", post(crate::squelch::squelch_account)
+            .delete(crate::squelch::unsquelch_account))
+        .route("/api/maintenance", get(crate::maintenance::list_windows).post(crate::maintenance::create_window))
+        .route("/api/maintenance/:id", get(crate::maintenance::get_window).delete(crate::maintenance::delete_window))
+        .route("/api/firmware", get(crate::firmware::get_inventory))
+        .route("/api/firmware/campaigns", get(crate::firmware::list_campaigns).post(crate::firmware::create_campaign))
+        .route("/api/firmware/campaigns/:id", get(crate::firmware::get_campaign).put(crate::firmware::update_campaign))
+        .route("/api/webhooks", get(crate::webhooks::list_webhooks).post(crate::webhooks::create_webhook))
+        .route("/api/webhooks/:id", get(crate::webhooks::get_webhook).delete(crate::webhooks::delete_webhook))
+        .route("/api/webhooks/:id/deliveries", get(crate::webhooks::list_deliveries))
+        .route("/api/deliveries/:id/retry", post(crate::webhooks::retry_delivery))
+        .route("/api/metrics/accounts", get(crate::metrics::get_account_metrics))
+        .route("/api/metrics/broker", get(crate::broker::get_broker_metrics))
+        .route("/api/metrics/slots", get(crate::intervals::get_slot_metrics))
+        .route("/api/stream", get(crate::stream::stream))
+        .route("/api/history/:mac", get(crate::history::get_device_history))
+        .route("/api/reports/uptime/devices/:mac", get(crate::reports::get_device_uptime))
+        .route("/api/reports/uptime/zones/:zone", get(crate::reports::get_zone_uptime))
+        .route("/api/reports/uptime/accounts/:account", get(crate::reports::get_account_uptime))
+        .route_layer(axum::middleware::from_fn_with_state(state, crate::auth::require_admin))
+}
+
 /// Create the Axum router with all routes
 pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/health", get(health))
+        .route("/ready", get(ready))
         .route("/api/db-info", get(get_db_info))
-        .route("/hbd", get(handle_heartbeat))
-        .route("/hbd/uninitialized", get(handle_heartbeat_uninitialized))
-        // .route("/api/heartbeat/procedure", post(call_stored_procedure))
+        .route("/hbd", get(handle_heartbeat).post(handle_heartbeat))
+        .route("/hbd/uninitialized", get(handle_heartbeat_uninitialized).post(handle_heartbeat_uninitialized))
+        .merge(admin_router(state.clone()))
         .layer(CorsLayer::permissive())
         .with_state(state)
 }
\ No newline at end of file
@@ -0,0 +1,488 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use mysql::prelude::*;
+use mysql::TxOpts;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::events::{DeviceEvent, EventKind};
+use crate::server::AppState;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const SQUELCH_COLUMNS: &str = "id, scope, mac_address, zone_number, account_id,
+    DATE_FORMAT(starts_at, '%Y-%m-%d %H:%i:%s'), DATE_FORMAT(ends_at, '%Y-%m-%d %H:%i:%s'), reason,
+    DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s'), DATE_FORMAT(lifted_at, '%Y-%m-%d %H:%i:%s')";
+
+/// Row shape produced by SQUELCH_COLUMNS
+type SquelchRow = (u64, String, Option<String>, Option<i32>, Option<i32>, Option<String>,
+    Option<String>, Option<String>, Option<String>, Option<String>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SquelchScope {
+    Device,
+    Zone,
+    Account,
+}
+
+impl SquelchScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SquelchScope::Device => "device",
+            SquelchScope::Zone => "zone",
+            SquelchScope::Account => "account",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "device" => Some(SquelchScope::Device),
+            "zone" => Some(SquelchScope::Zone),
+            "account" => Some(SquelchScope::Account),
+            _ => None,
+        }
+    }
+}
+
+/// A squelch on a device, a zone or an account, optionally limited to [starts_at, ends_at).
+/// Zone squelches with an account_id only cover that account's devices in the zone.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Squelch {
+    pub id: u64,
+    pub scope: SquelchScope,
+    pub mac_address: Option<String>,
+    pub zone_number: Option<i32>,
+    pub account_id: Option<i32>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+    pub created_at: Option<String>,
+    pub lifted_at: Option<String>,
+}
+
+impl Squelch {
+    /// True if `now` falls inside the squelch's schedule
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.lifted_at.is_none()
+            && self.starts_at.is_none_or(|start| start <= now)
+            && self.ends_at.is_none_or(|end| now < end)
+    }
+
+    /// True if the squelch covers a device with this MAC, zone and account
+    pub fn applies_to(&self, mac: &str, zone: Option<i32>, account: Option<i32>) -> bool {
+        match self.scope {
+            SquelchScope::Device => self.mac_address.as_deref().is_some_and(|m| m.eq_ignore_ascii_case(mac)),
+            SquelchScope::Zone => self.zone_number.is_some() && self.zone_number == zone
+                && self.account_id.is_none_or(|a| Some(a) == account),
+            SquelchScope::Account => self.account_id.is_some() && self.account_id == account,
+        }
+    }
+}
+
+fn parse_db_datetime(value: Option<String>) -> Option<DateTime<Utc>> {
+    value.and_then(|v| NaiveDateTime::parse_from_str(&v, DB_DATETIME_FORMAT).ok()).map(|dt| dt.and_utc())
+}
+
+fn squelch_from_row(row: mysql::Row) -> Option<Squelch> {
+    let (id, scope, mac_address, zone_number, account_id, starts_at, ends_at, reason, created_at, lifted_at): SquelchRow =
+        mysql::from_row(row);
+    Some(Squelch {
+        id,
+        scope: SquelchScope::parse(&scope)?,
+        mac_address,
+        zone_number,
+        account_id,
+        starts_at: parse_db_datetime(starts_at),
+        ends_at: parse_db_datetime(ends_at),
+        reason,
+        created_at,
+        lifted_at,
+    })
+}
+
+/// Squelches that are not lifted and have not yet ended
+fn load_pending(conn: &mut mysql::PooledConn) -> mysql::Result<Vec<Squelch>> {
+    let rows: Vec<mysql::Row> = conn.query(format!(
+        "SELECT {} FROM squelches WHERE lifted_at IS NULL AND (ends_at IS NULL OR ends_at > UTC_TIMESTAMP())",
+        SQUELCH_COLUMNS
+    ))?;
+    Ok(rows.into_iter().filter_map(squelch_from_row).collect())
+}
+
+/// In-memory copy of the pending squelches, consulted on every heartbeat.
+/// Schedules are evaluated at heartbeat time, so squelches start and expire on their own.
+pub struct SquelchSet {
+    squelches: RwLock<Vec<Squelch>>,
+}
+
+impl SquelchSet {
+    pub fn new() -> Self {
+        Self { squelches: RwLock::new(Vec::new()) }
+    }
+
+    pub fn replace(&self, squelches: Vec<Squelch>) {
+        *self.squelches.write().unwrap() = squelches;
+    }
+
+    /// Reload pending squelches from MySQL
+    pub fn reload(&self, conn: &mut mysql::PooledConn) -> mysql::Result<usize> {
+        let squelches = load_pending(conn)?;
+        let count = squelches.len();
+        self.replace(squelches);
+        Ok(count)
+    }
+
+    /// The active squelch covering this device, if any
+    pub fn find(&self, mac: &str, zone: Option<i32>, account: Option<i32>, now: DateTime<Utc>) -> Option<Squelch> {
+        self.squelches.read().unwrap().iter()
+            .find(|squelch| squelch.is_active(now) && squelch.applies_to(mac, zone, account))
+            .cloned()
+    }
+}
+
+/// Background task: reload squelches so changes made through other hbd instances are honoured
+pub async fn run_squelch_refresh(state: AppState, interval: Duration) {
+    loop {
+        let refresh_state = state.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut conn = refresh_state.get_read_connection()?;
+            refresh_state.squelches.reload(&mut conn).map_err(anyhow::Error::from)
+        }).await;
+        match result {
+            Ok(Ok(count)) => log::debug!("Loaded {} pending squelches", count),
+            Ok(Err(e)) => log::debug!("Squelch reload failed, keeping previous set: {:#}", e),
+            Err(e) => log::error!("Squelch reload task panicked: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Evict cached devices covered by `target` so their next heartbeat re-reads MySQL
+fn invalidate_cache(state: &AppState, target: &Squelch) {
+    for device in state.heart_beat_cache.devices() {
+        if target.applies_to(&device.mac_address, device.zone_number, device.account_id) {
+            state.heart_beat_cache.remove_device(&device.mac_address);
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SquelchRequest {
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SquelchQuery {
+    /// Include lifted and expired squelches (default: false)
+    pub all: Option<bool>,
+}
+
+/// Build the squelch a request targets, checking the caller may squelch it
+fn resolve_target(state: &AppState, principal: &Principal, scope: SquelchScope, target: &str) -> Result<Squelch, ApiError> {
+    let mut squelch = Squelch {
+        id: 0,
+        scope,
+        mac_address: None,
+        zone_number: None,
+        account_id: None,
+        starts_at: None,
+        ends_at: None,
+        reason: None,
+        created_at: None,
+        lifted_at: None,
+    };
+    match scope {
+        SquelchScope::Device => {
+            let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+            let device = crate::devices::find_device(&mut conn, target, 0)
+                .map_err(|e| ApiError::database("Failed to look up device", e))?
+                .filter(|device| principal.can_access(device.account_id))
+                .ok_or_else(|| ApiError::not_found(format!("Device {} not found", target)))?;
+            squelch.mac_address = Some(device.mac_address);
+            squelch.account_id = device.account_id;
+        },
+        SquelchScope::Zone => {
+            squelch.zone_number = Some(target.parse().map_err(|_| ApiError::bad_request("Invalid zone number"))?);
+            // Account tokens can only squelch their own devices in the zone
+            squelch.account_id = principal.account_id;
+        },
+        SquelchScope::Account => {
+            let account = target.parse().map_err(|_| ApiError::bad_request("Invalid account id"))?;
+            principal.authorize(Some(account))?;
+            squelch.account_id = Some(account);
+        },
+    }
+    Ok(squelch)
+}
+
+async fn create_squelch(
+    state: AppState,
+    principal: Principal,
+    scope: SquelchScope,
+    target: String,
+    request: SquelchRequest,
+) -> Result<(StatusCode, Json<Squelch>), ApiError> {
+    let now = Utc::now();
+    if let Some(end) = request.ends_at
+        && (end <= now || request.starts_at.is_some_and(|start| start >= end))
+    {
+        return Err(ApiError::bad_request("ends_at must be in the future and after starts_at"));
+    }
+
+    let mut squelch = resolve_target(&state, &principal, scope, &target)?;
+    squelch.starts_at = request.starts_at;
+    squelch.ends_at = request.ends_at;
+    squelch.reason = request.reason;
+
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    // The squelch and its event commit together
+    let mut tx = conn.start_transaction(TxOpts::default())
+        .map_err(|e| ApiError::database("Failed to start transaction", e))?;
+    tx.exec_drop(
+        "INSERT INTO squelches (scope, mac_address, zone_number, account_id, starts_at, ends_at, reason)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        (
+            scope.as_str(),
+            &squelch.mac_address,
+            squelch.zone_number,
+            squelch.account_id,
+            squelch.starts_at.map(|t| t.format(DB_DATETIME_FORMAT).to_string()),
+            squelch.ends_at.map(|t| t.format(DB_DATETIME_FORMAT).to_string()),
+            &squelch.reason,
+        ),
+    ).map_err(|e| ApiError::database("Failed to create squelch", e))?;
+    squelch.id = tx.last_insert_id().unwrap_or_default();
+    crate::outbox::insert(&mut tx, &DeviceEvent {
+        kind: EventKind::Squelched,
+        account_id: squelch.account_id,
+        mac_address: squelch.mac_address.clone(),
+        zone_number: squelch.zone_number,
+        global_ip_address: None,
+        devices: Vec::new(),
+        details: serde_json::json!({
+            "squelch_id": squelch.id,
+            "scope": scope,
+            "starts_at": squelch.starts_at,
+            "ends_at": squelch.ends_at,
+            "reason": squelch.reason
+        }),
+        at: now,
+    }).map_err(|e| ApiError::database("Failed to record squelch event", e))?;
+    tx.commit().map_err(|e| ApiError::database("Failed to create squelch", e))?;
+    state.outbox.notify();
+
+    if let Err(e) = state.squelches.reload(&mut conn) {
+        log::warn!("Failed to reload squelches: {}", e);
+    }
+    invalidate_cache(&state, &squelch);
+    log::info!("Squelch {} on {} {} created ({:?} - {:?}): {}", squelch.id, scope.as_str(), target,
+        squelch.starts_at, squelch.ends_at, squelch.reason.as_deref().unwrap_or(""));
+
+    let created = conn.exec_first(format!("SELECT {} FROM squelches WHERE id = ?", SQUELCH_COLUMNS), (squelch.id,))
+        .map_err(|e| ApiError::database("Failed to read created squelch", e))?
+        .and_then(squelch_from_row)
+        .unwrap_or(squelch);
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// Lift every pending squelch on the target
+async fn lift_squelches(
+    state: AppState,
+    principal: Principal,
+    scope: SquelchScope,
+    target: String,
+) -> Result<StatusCode, ApiError> {
+    let squelch = resolve_target(&state, &principal, scope, &target)?;
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    let mut filter = match scope {
+        SquelchScope::Device => "mac_address = ?",
+        SquelchScope::Zone => "zone_number = ?",
+        SquelchScope::Account => "account_id = ?",
+    }.to_string();
+    let mut values: Vec<mysql::Value> = vec![match scope {
+        SquelchScope::Device => squelch.mac_address.clone().into(),
+        SquelchScope::Zone => squelch.zone_number.into(),
+        SquelchScope::Account => squelch.account_id.into(),
+    }];
+    if scope == SquelchScope::Zone && let Some(account) = principal.account_id {
+        filter.push_str(" AND account_id = ?");
+        values.push(account.into());
+    }
+    values.insert(0, scope.as_str().into());
+    conn.exec_drop(
+        format!("UPDATE squelches SET lifted_at = UTC_TIMESTAMP() WHERE lifted_at IS NULL AND scope = ? AND {}", filter),
+        values,
+    ).map_err(|e| ApiError::database("Failed to lift squelch", e))?;
+    let lifted = conn.affected_rows();
+    if lifted == 0 {
+        return Err(ApiError::not_found(format!("No squelch on {} {}", scope.as_str(), target)));
+    }
+
+    if let Err(e) = state.squelches.reload(&mut conn) {
+        log::warn!("Failed to reload squelches: {}", e);
+    }
+    invalidate_cache(&state, &squelch);
+    log::info!("Lifted {} squelches on {} {}", lifted, scope.as_str(), target);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Squelch a device
+pub async fn squelch_device(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(mac): Path<String>,
+    request: Option<Json<SquelchRequest>>,
+) -> Result<(StatusCode, Json<Squelch>), ApiError> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    create_squelch(state, principal, SquelchScope::Device, mac, request).await
+}
+
+/// Lift a device's squelches
+pub async fn unsquelch_device(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(mac): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    lift_squelches(state, principal, SquelchScope::Device, mac).await
+}
+
+/// Squelch every device in a zone
+pub async fn squelch_zone(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(zone): Path<String>,
+    request: Option<Json<SquelchRequest>>,
+) -> Result<(StatusCode, Json<Squelch>), ApiError> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    create_squelch(state, principal, SquelchScope::Zone, zone, request).await
+}
+
+/// Lift a zone's squelches
+pub async fn unsquelch_zone(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(zone): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    lift_squelches(state, principal, SquelchScope::Zone, zone).await
+}
+
+/// Squelch every device in an account
+pub async fn squelch_account(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(account): Path<String>,
+    request: Option<Json<SquelchRequest>>,
+) -> Result<(StatusCode, Json<Squelch>), ApiError> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    create_squelch(state, principal, SquelchScope::Account, account, request).await
+}
+
+/// Lift an account's squelches
+pub async fn unsquelch_account(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(account): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    lift_squelches(state, principal, SquelchScope::Account, account).await
+}
+
+/// List squelches visible to the caller, pending ones only unless `all=true`
+pub async fn list_squelches(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<SquelchQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut filters = Vec::new();
+    let mut values: Vec<mysql::Value> = Vec::new();
+    if !params.all.unwrap_or(false) {
+        filters.push("lifted_at IS NULL AND (ends_at IS NULL OR ends_at > UTC_TIMESTAMP())");
+    }
+    if let Some(account) = principal.account_id {
+        filters.push("account_id = ?");
+        values.push(account.into());
+    }
+    let where_clause = if filters.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", filters.join(" AND "))
+    };
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let rows: Vec<mysql::Row> = conn.exec(
+        format!("SELECT {} FROM squelches {} ORDER BY id DESC", SQUELCH_COLUMNS, where_clause),
+        values,
+    ).map_err(|e| ApiError::database("Failed to list squelches", e))?;
+    let squelches: Vec<Squelch> = rows.into_iter().filter_map(squelch_from_row).collect();
+    Ok(Json(serde_json::json!({ "squelches": squelches })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn squelch(scope: SquelchScope) -> Squelch {
+        Squelch {
+            id: 1,
+            scope,
+            mac_address: None,
+            zone_number: None,
+            account_id: None,
+            starts_at: None,
+            ends_at: None,
+            reason: None,
+            created_at: None,
+            lifted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_schedule_window() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+        let scheduled = Squelch { starts_at: Some(start), ends_at: Some(end), ..squelch(SquelchScope::Account) };
+        assert!(!scheduled.is_active(start - chrono::Duration::seconds(1)));
+        assert!(scheduled.is_active(start));
+        assert!(!scheduled.is_active(end));
+        assert!(squelch(SquelchScope::Account).is_active(end));
+    }
+
+    #[test]
+    fn test_applies_to_scope() {
+        let device = Squelch { mac_address: Some("AA:BB".to_string()), ..squelch(SquelchScope::Device) };
+        assert!(device.applies_to("aa:bb", None, None));
+        assert!(!device.applies_to("aa:cc", None, None));
+
+        let zone = Squelch { zone_number: Some(3), account_id: Some(7), ..squelch(SquelchScope::Zone) };
+        assert!(zone.applies_to("aa:bb", Some(3), Some(7)));
+        assert!(!zone.applies_to("aa:bb", Some(3), Some(8)));
+        assert!(!zone.applies_to("aa:bb", Some(4), Some(7)));
+
+        let account = Squelch { account_id: Some(7), ..squelch(SquelchScope::Account) };
+        assert!(account.applies_to("aa:bb", None, Some(7)));
+        assert!(!account.applies_to("aa:bb", None, None));
+    }
+
+    #[test]
+    fn test_set_finds_active_squelch() {
+        let set = SquelchSet::new();
+        let now = Utc::now();
+        set.replace(vec![
+            Squelch { account_id: Some(7), starts_at: Some(now + chrono::Duration::hours(1)), ..squelch(SquelchScope::Account) },
+            Squelch { id: 2, zone_number: Some(3), ..squelch(SquelchScope::Zone) },
+        ]);
+        assert!(set.find("aa:bb", None, Some(7), now).is_none());
+        assert_eq!(set.find("aa:bb", Some(3), Some(7), now).map(|s| s.id), Some(2));
+    }
+}
@@ -0,0 +1,248 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::auth::Principal;
+use crate::events::DeviceEvent;
+use crate::server::AppState;
+
+/// A heartbeat as it passes through the heartbeat pipeline
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiveHeartbeat {
+    pub mac_address: String,
+    pub account_id: Option<i32>,
+    pub zone_number: Option<i32>,
+    pub local_ip_address: String,
+    pub global_ip_address: String,
+    pub squelched: bool,
+    pub at: DateTime<Utc>,
+}
+
+/// Fan-out of live heartbeats to WebSocket clients
+pub struct StreamHub {
+    heartbeats: broadcast::Sender<LiveHeartbeat>,
+}
+
+impl StreamHub {
+    pub fn new(capacity: usize) -> Self {
+        let (heartbeats, _) = broadcast::channel(capacity.max(1));
+        Self { heartbeats }
+    }
+
+    /// True while at least one client is connected, so the heartbeat path can skip building notices
+    pub fn has_subscribers(&self) -> bool {
+        self.heartbeats.receiver_count() > 0
+    }
+
+    pub fn heartbeat(&self, heartbeat: LiveHeartbeat) {
+        let _ = self.heartbeats.send(heartbeat);
+    }
+}
+
+/// Devices, zones and accounts a client is subscribed to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamFilter {
+    pub devices: BTreeSet<String>,
+    pub zones: BTreeSet<i32>,
+    pub accounts: BTreeSet<i32>,
+}
+
+impl StreamFilter {
+    fn normalize(mut self) -> Self {
+        self.devices = self.devices.into_iter().map(|mac| mac.to_uppercase()).collect();
+        self
+    }
+
+    fn add(&mut self, other: StreamFilter) {
+        self.devices.extend(other.devices);
+        self.zones.extend(other.zones);
+        self.accounts.extend(other.accounts);
+    }
+
+    fn remove(&mut self, other: &StreamFilter) {
+        self.devices.retain(|mac| !other.devices.contains(mac));
+        self.zones.retain(|zone| !other.zones.contains(zone));
+        self.accounts.retain(|account| !other.accounts.contains(account));
+    }
+
+    fn len(&self) -> usize {
+        self.devices.len() + self.zones.len() + self.accounts.len()
+    }
+
+    fn matches(&self, mac_address: Option<&str>, zone_number: Option<i32>, account_id: Option<i32>, devices: &[String]) -> bool {
+        mac_address.is_some_and(|mac| self.devices.contains(mac))
+            || devices.iter().any(|mac| self.devices.contains(mac))
+            || zone_number.is_some_and(|zone| self.zones.contains(&zone))
+            || account_id.is_some_and(|account| self.accounts.contains(&account))
+    }
+
+    fn matches_heartbeat(&self, heartbeat: &LiveHeartbeat) -> bool {
+        self.matches(Some(&heartbeat.mac_address), heartbeat.zone_number, heartbeat.account_id, &[])
+    }
+
+    fn matches_event(&self, event: &DeviceEvent) -> bool {
+        self.matches(event.mac_address.as_deref(), event.zone_number, event.account_id, &event.devices)
+    }
+}
+
+/// Messages from the client
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe(StreamFilter),
+    Unsubscribe(StreamFilter),
+}
+
+/// Messages to the client
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    /// The subscriptions now in effect
+    Subscribed { filter: &'a StreamFilter },
+    Heartbeat { heartbeat: LiveHeartbeat },
+    Event { event: DeviceEvent },
+    /// The client fell behind and `missed` messages were skipped; re-read state through the REST API
+    Lagged { missed: u64 },
+    Error { message: String },
+}
+
+impl ServerMessage<'_> {
+    fn into_message(self) -> Message {
+        Message::Text(serde_json::to_string(&self).expect("stream messages serialize to JSON"))
+    }
+}
+
+/// Apply a subscribe/unsubscribe request. Account tokens may only subscribe to their own
+/// account; device and zone subscriptions only ever see that account's devices.
+fn apply_request(filter: &mut StreamFilter, principal: &Principal, text: &str, max_subscriptions: usize) -> Result<(), String> {
+    let request: ClientMessage = serde_json::from_str(text).map_err(|e| format!("Invalid request: {}", e))?;
+    match request {
+        ClientMessage::Subscribe(added) => {
+            let added = added.normalize();
+            if let Some(account) = added.accounts.iter().find(|account| !principal.can_access(Some(**account))) {
+                return Err(format!("Account {} is not accessible with this token", account));
+            }
+            let mut updated = filter.clone();
+            updated.add(added);
+            if updated.len() > max_subscriptions {
+                return Err(format!("At most {} subscriptions per connection", max_subscriptions));
+            }
+            *filter = updated;
+        },
+        ClientMessage::Unsubscribe(removed) => filter.remove(&removed.normalize()),
+    }
+    Ok(())
+}
+
+/// Live device status over WebSocket.
+/// Clients send `{"action": "subscribe", "devices": [...], "zones": [...], "accounts": [...]}`
+/// (or "unsubscribe") and receive heartbeats and device/site events for their subscriptions.
+pub async fn stream(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| serve(socket, state, principal))
+}
+
+async fn serve(mut socket: WebSocket, state: AppState, principal: Principal) {
+    let config = state.config.stream.clone();
+    let mut heartbeats = state.stream.heartbeats.subscribe();
+    let mut events = state.events.subscribe();
+    let mut filter = StreamFilter::default();
+    let mut ping = tokio::time::interval(Duration::from_secs(config.ping_interval.max(1)));
+    log::info!("Stream client connected (account {:?})", principal.account_id);
+
+    loop {
+        let outgoing = tokio::select! {
+            received = socket.recv() => match received {
+                Some(Ok(Message::Text(text))) => Some(match apply_request(&mut filter, &principal, &text, config.max_subscriptions) {
+                    Ok(()) => ServerMessage::Subscribed { filter: &filter }.into_message(),
+                    Err(message) => ServerMessage::Error { message }.into_message(),
+                }),
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => None,
+                Some(Err(e)) => {
+                    log::debug!("Stream client error: {}", e);
+                    break;
+                },
+            },
+            heartbeat = heartbeats.recv() => match heartbeat {
+                Ok(heartbeat) if principal.can_access(heartbeat.account_id) && filter.matches_heartbeat(&heartbeat) =>
+                    Some(ServerMessage::Heartbeat { heartbeat }.into_message()),
+                Ok(_) => None,
+                Err(broadcast::error::RecvError::Lagged(missed)) => Some(ServerMessage::Lagged { missed }.into_message()),
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            event = events.recv() => match event {
+                Ok(event) if principal.can_access(event.account_id) && filter.matches_event(&event) =>
+                    Some(ServerMessage::Event { event }.into_message()),
+                Ok(_) => None,
+                Err(broadcast::error::RecvError::Lagged(missed)) => Some(ServerMessage::Lagged { missed }.into_message()),
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = ping.tick() => Some(Message::Ping(Vec::new())),
+        };
+
+        // A client that cannot take a message within send_timeout is disconnected;
+        // shorter stalls are absorbed by the channel buffers and reported as lagged
+        if let Some(message) = outgoing {
+            match tokio::time::timeout(Duration::from_secs(config.send_timeout), socket.send(message)).await {
+                Ok(Ok(())) => {},
+                Ok(Err(e)) => {
+                    log::debug!("Stream client send failed: {}", e);
+                    break;
+                },
+                Err(_) => {
+                    log::warn!("Disconnecting stream client (account {:?}) that stopped reading", principal.account_id);
+                    break;
+                },
+            }
+        }
+    }
+    log::info!("Stream client disconnected (account {:?})", principal.account_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(mac: &str, zone: Option<i32>, account: Option<i32>) -> LiveHeartbeat {
+        LiveHeartbeat {
+            mac_address: mac.to_string(),
+            account_id: account,
+            zone_number: zone,
+            local_ip_address: "10.0.0.2".to_string(),
+            global_ip_address: "203.0.113.7".to_string(),
+            squelched: false,
+            at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_subscriptions_and_scoping() {
+        let tenant = Principal { account_id: Some(3) };
+        let mut filter = StreamFilter::default();
+        apply_request(&mut filter, &tenant, r#"{"action":"subscribe","devices":["aa:bb"],"zones":[5]}"#, 10).unwrap();
+        assert!(filter.matches_heartbeat(&heartbeat("AA:BB", None, Some(3))));
+        assert!(filter.matches_heartbeat(&heartbeat("CC:DD", Some(5), Some(3))));
+        assert!(!filter.matches_heartbeat(&heartbeat("CC:DD", Some(6), Some(3))));
+
+        assert!(apply_request(&mut filter, &tenant, r#"{"action":"subscribe","accounts":[4]}"#, 10).is_err());
+        assert!(apply_request(&mut filter, &tenant, r#"{"action":"subscribe","zones":[1,2,3,4,6,7,8,9,10]}"#, 10).is_err());
+
+        apply_request(&mut filter, &tenant, r#"{"action":"unsubscribe","devices":["AA:BB"]}"#, 10).unwrap();
+        assert!(!filter.matches_heartbeat(&heartbeat("AA:BB", None, Some(3))));
+    }
+}
@@ -0,0 +1,202 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use mysql::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::server::AppState;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Longest firmware version stored (device_telemetry.firmware_version)
+const MAX_FIRMWARE_VERSION_LEN: usize = 64;
+
+/// Health readings a device may send with its heartbeat, as query parameters
+/// or a JSON body. Every field is optional; unparseable telemetry is ignored
+/// rather than failing the heartbeat.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Telemetry {
+    pub firmware_version: Option<String>,
+    /// Seconds since the device booted
+    pub uptime: Option<u64>,
+    /// CPU temperature in degrees Celsius
+    pub cpu_temp: Option<f32>,
+    /// Free storage in bytes
+    pub free_storage: Option<u64>,
+    /// Wi-Fi signal strength in dBm
+    pub rssi: Option<i32>,
+}
+
+impl Telemetry {
+    pub fn is_empty(&self) -> bool {
+        *self == Telemetry::default()
+    }
+
+    /// Readings from heartbeat query parameters, each parsed on its own so one
+    /// malformed field does not cost the others
+    pub fn from_query(params: &HashMap<String, String>) -> Telemetry {
+        fn field<T: std::str::FromStr>(params: &HashMap<String, String>, name: &str) -> Option<T> {
+            params.get(name)?.trim().parse().ok()
+        }
+        Telemetry {
+            firmware_version: params.get("firmware_version").cloned(),
+            uptime: field(params, "uptime"),
+            cpu_temp: field(params, "cpu_temp"),
+            free_storage: field(params, "free_storage"),
+            rssi: field(params, "rssi"),
+        }
+    }
+
+    /// Drop readings that cannot be stored
+    pub fn sanitize(mut self) -> Self {
+        self.firmware_version = self.firmware_version
+            .map(|version| version.trim().to_string())
+            .filter(|version| !version.is_empty() && version.len() <= MAX_FIRMWARE_VERSION_LEN);
+        self.cpu_temp = self.cpu_temp.filter(|temp| temp.is_finite());
+        self
+    }
+
+    /// These readings with `newer` ones on top; fields the newer report leaves out keep their value
+    pub fn merge(self, newer: Telemetry) -> Telemetry {
+        Telemetry {
+            firmware_version: newer.firmware_version.or(self.firmware_version),
+            uptime: newer.uptime.or(self.uptime),
+            cpu_temp: newer.cpu_temp.or(self.cpu_temp),
+            free_storage: newer.free_storage.or(self.free_storage),
+            rssi: newer.rssi.or(self.rssi),
+        }
+    }
+
+    /// Whether these readings are worth writing over `previous`. Uptime grows on every
+    /// heartbeat, so it only counts when it goes backwards, i.e. the device rebooted.
+    pub fn changed_from(&self, previous: &Telemetry) -> bool {
+        self.must_write_over(previous)
+            || self.cpu_temp != previous.cpu_temp
+            || self.free_storage != previous.free_storage
+            || self.rssi != previous.rssi
+    }
+
+    /// Whether these readings must be written at once: new firmware or a reboot.
+    /// The other readings change on almost every heartbeat and wait for the next
+    /// heartbeat write instead.
+    pub fn must_write_over(&self, previous: &Telemetry) -> bool {
+        self.firmware_version != previous.firmware_version
+            || matches!((self.uptime, previous.uptime), (Some(now), Some(before)) if now < before)
+            || (self.uptime.is_some() && previous.uptime.is_none())
+    }
+}
+
+/// Store a device's latest telemetry, inside the caller's transaction when given one
+pub fn persist<Q: Queryable>(conn: &mut Q, mac_address: &str, telemetry: &Telemetry, reported_at: DateTime<Utc>) -> mysql::Result<()> {
+    conn.exec_drop(
+        "INSERT INTO device_telemetry
+            (mac_address, firmware_version, uptime_seconds, cpu_temperature, free_storage_bytes, rssi, reported_at)
+         VALUES (UPPER(?), ?, ?, ?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE
+            firmware_version = VALUES(firmware_version),
+            uptime_seconds = VALUES(uptime_seconds),
+            cpu_temperature = VALUES(cpu_temperature),
+            free_storage_bytes = VALUES(free_storage_bytes),
+            rssi = VALUES(rssi),
+            reported_at = VALUES(reported_at)",
+        (mac_address, &telemetry.firmware_version, telemetry.uptime, telemetry.cpu_temp, telemetry.free_storage,
+         telemetry.rssi, reported_at.format(DB_DATETIME_FORMAT).to_string()),
+    )
+}
+
+type TelemetryRow = (Option<String>, Option<u64>, Option<f32>, Option<u64>, Option<i32>, String);
+
+fn find_telemetry(conn: &mut mysql::PooledConn, mac: &str) -> mysql::Result<Option<(Telemetry, Option<DateTime<Utc>>)>> {
+    let row: Option<TelemetryRow> = conn.exec_first(
+        "SELECT firmware_version, uptime_seconds, cpu_temperature, free_storage_bytes, rssi,
+                DATE_FORMAT(reported_at, '%Y-%m-%d %H:%i:%s')
+         FROM device_telemetry WHERE mac_address = UPPER(?)",
+        (mac,),
+    )?;
+    Ok(row.map(|(firmware_version, uptime, cpu_temp, free_storage, rssi, reported_at)| (
+        Telemetry { firmware_version, uptime, cpu_temp, free_storage, rssi },
+        NaiveDateTime::parse_from_str(&reported_at, DB_DATETIME_FORMAT).ok().map(|at| at.and_utc()),
+    )))
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceTelemetry {
+    pub mac_address: String,
+    #[serde(flatten)]
+    pub telemetry: Telemetry,
+    /// Last heartbeat that carried telemetry
+    pub reported_at: Option<DateTime<Utc>>,
+}
+
+/// Latest telemetry of a device: from the heartbeat cache when it has reported since
+/// startup, otherwise as last written to MySQL. Devices that never sent any return all nulls.
+pub async fn get_device_telemetry(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(mac): Path<String>,
+) -> Result<Json<DeviceTelemetry>, ApiError> {
+    let mac_address = mac.to_uppercase();
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    crate::devices::find_device(&mut conn, &mac_address, 0)
+        .map_err(|e| ApiError::database("Failed to get device", e))?
+        .filter(|device| principal.can_access(device.account_id))
+        .ok_or_else(|| ApiError::not_found(format!("Device {} not found", mac_address)))?;
+
+    let cached = state.heart_beat_cache.get_device(&mac_address)
+        .filter(|cached| cached.telemetry_reported_at.is_some());
+    let (telemetry, reported_at) = match cached {
+        Some(cached) => (cached.telemetry, cached.telemetry_reported_at),
+        None => find_telemetry(&mut conn, &mac_address)
+            .map_err(|e| ApiError::database("Failed to read telemetry", e))?
+            .unwrap_or_default(),
+    };
+    Ok(Json(DeviceTelemetry { mac_address, telemetry, reported_at }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_and_change_detection() {
+        let uri = "/hbd?MAC=AA&firmware_version=2.1.0&uptime=600&cpu_temp=51.5&rssi=-62".parse().unwrap();
+        let axum::extract::Query(query) = axum::extract::Query::<Telemetry>::try_from_uri(&uri).unwrap();
+        assert_eq!(query.uptime, Some(600));
+        assert_eq!(query.rssi, Some(-62));
+
+        let previous = query.clone().sanitize();
+        let later = previous.clone().merge(Telemetry { uptime: Some(900), ..Telemetry::default() });
+        assert_eq!(later.firmware_version.as_deref(), Some("2.1.0"));
+        assert!(!later.changed_from(&previous));
+
+        let rebooted = later.clone().merge(Telemetry { uptime: Some(30), ..Telemetry::default() });
+        assert!(rebooted.changed_from(&later));
+        assert!(rebooted.must_write_over(&later));
+        let hotter = later.clone().merge(Telemetry { cpu_temp: Some(70.0), ..Telemetry::default() });
+        assert!(hotter.changed_from(&later));
+        assert!(!hotter.must_write_over(&later));
+        let upgraded = later.clone().merge(Telemetry { firmware_version: Some("2.2.0".to_string()), ..Telemetry::default() });
+        assert!(upgraded.must_write_over(&later));
+
+        let junk = Telemetry { firmware_version: Some(" ".to_string()), cpu_temp: Some(f32::NAN), ..Telemetry::default() };
+        assert!(junk.sanitize().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_query_field_is_ignored_alone() {
+        let uri = "/hbd?MAC=AA&firmware_version=2.1.0&uptime=soon&cpu_temp=51.5&rssi=-62".parse().unwrap();
+        let axum::extract::Query(params) = axum::extract::Query::<HashMap<String, String>>::try_from_uri(&uri).unwrap();
+        assert_eq!(Telemetry::from_query(&params), Telemetry {
+            firmware_version: Some("2.1.0".to_string()),
+            uptime: None,
+            cpu_temp: Some(51.5),
+            free_storage: None,
+            rssi: Some(-62),
+        });
+    }
+}
@@ -0,0 +1,227 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use mysql::prelude::*;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::server::{AppState, CreateUserRequest, User, UserQuery};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 1000;
+/// MySQL error code for duplicate unique keys
+const ER_DUP_ENTRY: u16 = 1062;
+/// Random bytes in an issued token, hex-encoded to twice as many characters
+const TOKEN_BYTES: usize = 32;
+
+const USER_COLUMNS: &str = "id, name, email, account_id, DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s')";
+
+fn user_from_row((id, name, email, account_id, created_at): (u32, String, String, Option<i32>, Option<String>)) -> User {
+    User {
+        id: Some(id),
+        name,
+        email,
+        account_id,
+        created_at,
+        token: None,
+    }
+}
+
+fn find_user(conn: &mut mysql::PooledConn, id: u32) -> mysql::Result<Option<User>> {
+    let row = conn.exec_first(format!("SELECT {} FROM users WHERE id = ?", USER_COLUMNS), (id,))?;
+    Ok(row.map(user_from_row))
+}
+
+/// Tokens are stored as their SHA-256, so a leaked users table grants no access
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// A new random bearer token
+fn generate_token() -> std::io::Result<String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(hex::encode(bytes))
+}
+
+fn issue_token() -> Result<String, ApiError> {
+    generate_token().map_err(|e| {
+        log::error!("Failed to generate user token: {}", e);
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", "Failed to generate a token")
+    })
+}
+
+/// The principal of the user holding `token`, if any. Read from the primary so
+/// deleted users and rotated tokens stop working at once.
+pub fn find_principal(state: &AppState, token: &str) -> Result<Option<Principal>, ApiError> {
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    let row: Option<(Option<i32>,)> = conn.exec_first("SELECT account_id FROM users WHERE token_hash = ?", (hash_token(token),))
+        .map_err(|e| ApiError::database("Failed to look up user token", e))?;
+    Ok(row.map(|(account_id,)| Principal { account_id }))
+}
+
+/// Check a new user: a name, a plausible email and an account the caller may grant.
+/// Returns the trimmed name and lower-cased email.
+fn validate_create(principal: &Principal, payload: &CreateUserRequest) -> Result<(String, String), ApiError> {
+    principal.authorize(payload.account_id)?;
+    let name = payload.name.trim();
+    let email = payload.email.trim().to_lowercase();
+    if name.is_empty() || !email.contains('@') {
+        return Err(ApiError::bad_request("name and a valid email are required"));
+    }
+    Ok((name.to_string(), email))
+}
+
+/// Create an operator account and issue its token, which is only shown in this response.
+/// Account-scoped callers create users for their own account.
+pub async fn create_user(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<CreateUserRequest>,
+) -> Result<(StatusCode, Json<User>), ApiError> {
+    let (name, email) = validate_create(&principal, &payload)?;
+    let token = issue_token()?;
+
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    match conn.exec_drop(
+        "INSERT INTO users (name, email, account_id, token_hash) VALUES (?, ?, ?, ?)",
+        (&name, &email, payload.account_id, hash_token(&token)),
+    ) {
+        Err(mysql::Error::MySqlError(e)) if e.code == ER_DUP_ENTRY => {
+            return Err(ApiError::new(StatusCode::CONFLICT, "conflict", format!("A user with email {} already exists", email)));
+        },
+        Err(e) => return Err(ApiError::database("Failed to create user", e)),
+        Ok(()) => {},
+    }
+
+    let id = conn.last_insert_id() as u32;
+    log::info!("User {} <{}> created", id, email);
+    find_user(&mut conn, id)
+        .map_err(|e| ApiError::database("Failed to read created user", e))?
+        .map(|user| (StatusCode::CREATED, Json(User { token: Some(token), ..user })))
+        .ok_or_else(|| ApiError::not_found(format!("User {} not found after creation", id)))
+}
+
+/// List operator accounts with pagination; account-scoped callers see their account's
+pub async fn list_users(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<UserQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = params.offset.unwrap_or(0);
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let total: Option<u64> = conn.exec_first(
+        "SELECT COUNT(*) FROM users WHERE ? IS NULL OR account_id = ?",
+        (principal.account_id, principal.account_id),
+    ).map_err(|e| ApiError::database("Failed to count users", e))?;
+    let users: Vec<User> = conn.exec_map(
+        format!("SELECT {} FROM users WHERE ? IS NULL OR account_id = ? ORDER BY id LIMIT ? OFFSET ?", USER_COLUMNS),
+        (principal.account_id, principal.account_id, limit, offset),
+        user_from_row,
+    ).map_err(|e| ApiError::database("Failed to list users", e))?;
+
+    Ok(Json(serde_json::json!({
+        "users": users,
+        "total": total.unwrap_or(0),
+        "limit": limit,
+        "offset": offset
+    })))
+}
+
+/// A user the caller may manage
+fn find_visible_user(conn: &mut mysql::PooledConn, principal: &Principal, id: u32) -> Result<User, ApiError> {
+    find_user(conn, id)
+        .map_err(|e| ApiError::database("Failed to get user", e))?
+        .filter(|user| principal.can_access(user.account_id))
+        .ok_or_else(|| ApiError::not_found(format!("User {} not found", id)))
+}
+
+/// Get an operator account by id
+pub async fn get_user(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u32>,
+) -> Result<Json<User>, ApiError> {
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_visible_user(&mut conn, &principal, id).map(Json)
+}
+
+/// Issue a new token for a user; the previous one stops working
+pub async fn rotate_token(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u32>,
+) -> Result<Json<User>, ApiError> {
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    let user = find_visible_user(&mut conn, &principal, id)?;
+    let token = issue_token()?;
+    conn.exec_drop("UPDATE users SET token_hash = ? WHERE id = ?", (hash_token(&token), id))
+        .map_err(|e| ApiError::database("Failed to rotate user token", e))?;
+    log::info!("Token of user {} rotated", id);
+    Ok(Json(User { token: Some(token), ..user }))
+}
+
+/// Delete an operator account, revoking its token
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u32>,
+) -> Result<StatusCode, ApiError> {
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_visible_user(&mut conn, &principal, id)?;
+    conn.exec_drop("DELETE FROM users WHERE id = ?", (id,))
+        .map_err(|e| ApiError::database("Failed to delete user", e))?;
+    if conn.affected_rows() == 0 {
+        return Err(ApiError::not_found(format!("User {} not found", id)));
+    }
+    log::info!("User {} deleted", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(name: &str, email: &str, account_id: Option<i32>) -> CreateUserRequest {
+        CreateUserRequest { name: name.to_string(), email: email.to_string(), account_id }
+    }
+
+    #[test]
+    fn test_validation() {
+        let admin = Principal { account_id: None };
+        assert_eq!(
+            validate_create(&admin, &request(" Ada ", " Ada@Example.COM ", None)).unwrap(),
+            ("Ada".to_string(), "ada@example.com".to_string())
+        );
+        assert_eq!(validate_create(&admin, &request("  ", "ada@example.com", None)).unwrap_err().status, StatusCode::BAD_REQUEST);
+        assert_eq!(validate_create(&admin, &request("Ada", "ada.example.com", None)).unwrap_err().status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_scope() {
+        let admin = Principal { account_id: None };
+        let tenant = Principal { account_id: Some(7) };
+        assert!(validate_create(&admin, &request("Ada", "ada@example.com", Some(7))).is_ok());
+        assert!(validate_create(&tenant, &request("Ada", "ada@example.com", Some(7))).is_ok());
+        // Tenants cannot mint fleet-wide or other accounts' operators
+        assert_eq!(validate_create(&tenant, &request("Ada", "ada@example.com", None)).unwrap_err().status, StatusCode::FORBIDDEN);
+        assert_eq!(validate_create(&tenant, &request("Ada", "ada@example.com", Some(8))).unwrap_err().status, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_tokens() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert_ne!(token, generate_token().unwrap());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_eq!(hash_token(&token).len(), 64);
+        assert_ne!(hash_token(&token), token);
+    }
+}
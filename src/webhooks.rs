@@ -0,0 +1,559 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::Json,
+    Extension,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use mysql::prelude::*;
+use mysql::TxOpts;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::task::JoinSet;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::events::{DeviceEvent, EventKind};
+use crate::server::AppState;
+
+/// How long the in-memory webhook list is used before MySQL is read again
+const REGISTRY_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 1000;
+const MIN_SECRET_LENGTH: usize = 16;
+/// last_error of deliveries dead-lettered because their webhook is gone
+const WEBHOOK_DELETED: &str = "webhook was deleted";
+
+const WEBHOOK_COLUMNS: &str = "id, account_id, url, secret, event_types, DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s')";
+
+const DELIVERY_COLUMNS: &str = "id, webhook_id, event_kind, payload, status, attempts, last_status_code, last_error,
+    DATE_FORMAT(next_attempt_at, '%Y-%m-%d %H:%i:%s'), DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s'),
+    DATE_FORMAT(delivered_at, '%Y-%m-%d %H:%i:%s')";
+
+/// Row shape produced by DELIVERY_COLUMNS
+type DeliveryRow = (u64, u64, String, String, String, u32, Option<u16>, Option<String>,
+    Option<String>, Option<String>, Option<String>);
+
+/// A registered webhook. Webhooks without an account receive events for every account.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Webhook {
+    pub id: u64,
+    pub account_id: Option<i32>,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub event_types: Vec<EventKind>,
+    pub created_at: Option<String>,
+}
+
+impl Webhook {
+    pub fn matches(&self, event: &DeviceEvent) -> bool {
+        self.event_types.contains(&event.kind)
+            && self.account_id.is_none_or(|account| event.account_id == Some(account))
+    }
+}
+
+fn webhook_from_row(
+    (id, account_id, url, secret, event_types, created_at): (u64, Option<i32>, String, String, String, Option<String>),
+) -> Webhook {
+    Webhook {
+        id,
+        account_id,
+        url,
+        secret,
+        event_types: event_types.split(',').filter_map(|kind| EventKind::parse(kind.trim())).collect(),
+        created_at,
+    }
+}
+
+/// One entry in the delivery log
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: u64,
+    pub webhook_id: u64,
+    pub event_kind: String,
+    pub payload: serde_json::Value,
+    /// pending, delivered or dead
+    pub status: String,
+    pub attempts: u32,
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: Option<String>,
+    pub created_at: Option<String>,
+    pub delivered_at: Option<String>,
+}
+
+fn delivery_from_row(row: mysql::Row) -> WebhookDelivery {
+    let (id, webhook_id, event_kind, payload, status, attempts, last_status_code, last_error,
+        next_attempt_at, created_at, delivered_at): DeliveryRow = mysql::from_row(row);
+    WebhookDelivery {
+        id,
+        webhook_id,
+        event_kind,
+        payload: serde_json::from_str(&payload).unwrap_or(serde_json::Value::String(payload)),
+        status,
+        attempts,
+        last_status_code,
+        last_error,
+        next_attempt_at,
+        created_at,
+        delivered_at,
+    }
+}
+
+/// `sha256=<hex>` HMAC of "<timestamp>.<body>", sent as X-Hbd-Signature
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Registered webhooks, cached in memory for matching events
+pub struct WebhookRegistry {
+    hooks: RwLock<Vec<Webhook>>,
+    loaded_at: RwLock<Option<Instant>>,
+    /// Wakes the delivery task when new deliveries are queued
+    due: Notify,
+}
+
+impl WebhookRegistry {
+    pub fn new() -> Self {
+        Self {
+            hooks: RwLock::new(Vec::new()),
+            loaded_at: RwLock::new(None),
+            due: Notify::new(),
+        }
+    }
+
+    pub fn reload<Q: Queryable>(&self, conn: &mut Q) -> mysql::Result<()> {
+        let hooks = conn.query_map(format!("SELECT {} FROM webhooks", WEBHOOK_COLUMNS), webhook_from_row)?;
+        *self.hooks.write().unwrap() = hooks;
+        *self.loaded_at.write().unwrap() = Some(Instant::now());
+        Ok(())
+    }
+
+    fn is_stale(&self) -> bool {
+        self.loaded_at.read().unwrap().is_none_or(|loaded| loaded.elapsed() >= REGISTRY_TTL)
+    }
+
+    pub fn matching(&self, event: &DeviceEvent) -> Vec<Webhook> {
+        self.hooks.read().unwrap().iter().filter(|hook| hook.matches(event)).cloned().collect()
+    }
+
+    /// Wake the delivery task after deliveries were queued
+    pub fn wake(&self) {
+        self.due.notify_one();
+    }
+}
+
+/// Record a pending delivery for every webhook subscribed to the event.
+/// Called by the outbox relay inside the transaction that marks the event published.
+pub fn enqueue<Q: Queryable>(state: &AppState, conn: &mut Q, event: &DeviceEvent) -> anyhow::Result<usize> {
+    if state.webhooks.is_stale() {
+        state.webhooks.reload(conn)?;
+    }
+    let hooks = state.webhooks.matching(event);
+    if hooks.is_empty() {
+        return Ok(0);
+    }
+    let payload = serde_json::to_string(event)?;
+    conn.exec_batch(
+        "INSERT INTO webhook_deliveries (webhook_id, event_kind, payload, next_attempt_at)
+         VALUES (?, ?, ?, UTC_TIMESTAMP())",
+        hooks.iter().map(|hook| (hook.id, event.kind.as_str(), &payload)),
+    )?;
+    log::debug!("Queued {} webhook deliveries for {}", hooks.len(), event.kind.as_str());
+    Ok(hooks.len())
+}
+
+/// Due delivery with its webhook's URL and secret, NULL once the webhook is deleted
+type DueRow = (u64, String, String, u32, Option<String>, Option<String>);
+
+/// A delivery claimed for one attempt
+struct ClaimedDelivery {
+    id: u64,
+    event_kind: String,
+    payload: String,
+    attempts: u32,
+    /// URL and secret of its webhook as stored when claimed, None once the webhook is deleted
+    target: Option<(String, String)>,
+}
+
+/// What became of one delivery attempt
+#[derive(Debug, Clone, PartialEq)]
+enum AttemptOutcome {
+    Delivered(u16),
+    /// The HTTP status, if a response arrived, and the error
+    Failed(Option<u16>, String),
+    /// The webhook no longer exists; the delivery is dead-lettered without an attempt
+    WebhookDeleted,
+}
+
+/// Claim deliveries that are due. Claimed rows are leased by pushing next_attempt_at
+/// forward, so other hbd instances skip them while this attempt is in flight.
+/// The webhook is read with each delivery rather than from the registry, which may not
+/// yet know about webhooks created or deleted through other instances.
+fn claim_due(state: &AppState, batch_size: usize, lease: Duration) -> anyhow::Result<Vec<ClaimedDelivery>> {
+    let mut conn = state.get_connection()?;
+    let due: Vec<DueRow> = conn.exec(
+        "SELECT d.id, d.event_kind, d.payload, d.attempts, w.url, w.secret
+         FROM webhook_deliveries d LEFT JOIN webhooks w ON w.id = d.webhook_id
+         WHERE d.status = 'pending' AND d.next_attempt_at <= UTC_TIMESTAMP()
+         ORDER BY d.id LIMIT ?",
+        (batch_size,),
+    )?;
+    let mut claimed = Vec::new();
+    for (id, event_kind, payload, attempts, url, secret) in due {
+        conn.exec_drop(
+            "UPDATE webhook_deliveries SET next_attempt_at = UTC_TIMESTAMP() + INTERVAL ? SECOND
+             WHERE id = ? AND status = 'pending' AND next_attempt_at <= UTC_TIMESTAMP()",
+            (lease.as_secs(), id),
+        )?;
+        if conn.affected_rows() == 1 {
+            claimed.push(ClaimedDelivery { id, event_kind, payload, attempts, target: url.zip(secret) });
+        }
+    }
+    Ok(claimed)
+}
+
+/// POST one delivery to its webhook
+async fn attempt(client: &reqwest::Client, delivery: &ClaimedDelivery) -> AttemptOutcome {
+    let Some((url, secret)) = &delivery.target else {
+        return AttemptOutcome::WebhookDeleted;
+    };
+    let event: serde_json::Value = serde_json::from_str(&delivery.payload).unwrap_or(serde_json::Value::Null);
+    let body = serde_json::json!({ "delivery_id": delivery.id, "event": event }).to_string();
+    let timestamp = Utc::now().timestamp();
+    let response = client.post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("X-Hbd-Event", &delivery.event_kind)
+        .header("X-Hbd-Delivery", delivery.id)
+        .header("X-Hbd-Timestamp", timestamp)
+        .header("X-Hbd-Signature", sign(secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => AttemptOutcome::Delivered(response.status().as_u16()),
+        Ok(response) => {
+            let status = response.status().as_u16();
+            AttemptOutcome::Failed(Some(status), format!("HTTP {}", status))
+        },
+        Err(e) => AttemptOutcome::Failed(None, e.to_string()),
+    }
+}
+
+/// Store the outcome of an attempt, scheduling a retry or dead-lettering the delivery
+fn record_attempt(state: &AppState, delivery: &ClaimedDelivery, outcome: AttemptOutcome) -> anyhow::Result<()> {
+    let config = &state.config.webhooks;
+    let attempts = delivery.attempts + 1;
+    let mut conn = state.get_connection()?;
+    match outcome {
+        AttemptOutcome::Delivered(status) => conn.exec_drop(
+            "UPDATE webhook_deliveries SET status = 'delivered', attempts = ?, last_status_code = ?,
+                last_error = NULL, delivered_at = UTC_TIMESTAMP()
+             WHERE id = ?",
+            (attempts, status, delivery.id),
+        )?,
+        AttemptOutcome::WebhookDeleted => {
+            log::info!("Webhook delivery {} dead-lettered: its webhook was deleted", delivery.id);
+            conn.exec_drop(
+                "UPDATE webhook_deliveries SET status = 'dead', last_error = ? WHERE id = ?",
+                (WEBHOOK_DELETED, delivery.id),
+            )?
+        },
+        AttemptOutcome::Failed(status, error) if attempts >= config.max_attempts => {
+            log::warn!("Webhook delivery {} dead-lettered after {} attempts: {}", delivery.id, attempts, error);
+            conn.exec_drop(
+                "UPDATE webhook_deliveries SET status = 'dead', attempts = ?, last_status_code = ?, last_error = ?
+                 WHERE id = ?",
+                (attempts, status, &error, delivery.id),
+            )?
+        },
+        AttemptOutcome::Failed(status, error) => {
+            let delay = config.backoff().delay_for_attempt(attempts);
+            log::debug!("Webhook delivery {} failed ({}), retrying in {:?}", delivery.id, error, delay);
+            conn.exec_drop(
+                "UPDATE webhook_deliveries SET attempts = ?, last_status_code = ?, last_error = ?,
+                    next_attempt_at = UTC_TIMESTAMP() + INTERVAL ? SECOND
+                 WHERE id = ?",
+                (attempts, status, &error, delay.as_secs(), delivery.id),
+            )?
+        },
+    }
+    Ok(())
+}
+
+/// Background task: attempt due deliveries, woken by new deliveries or every poll_interval
+pub async fn run_webhook_delivery(state: AppState) {
+    let config = state.config.webhooks.clone();
+    let timeout = Duration::from_secs(config.request_timeout);
+    let client = match reqwest::Client::builder().timeout(timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to create webhook HTTP client, webhooks are disabled: {}", e);
+            return;
+        }
+    };
+    let poll_interval = Duration::from_secs(config.poll_interval);
+    loop {
+        tokio::select! {
+            _ = state.webhooks.due.notified() => {},
+            _ = tokio::time::sleep(poll_interval) => {},
+        }
+
+        let claim_state = state.clone();
+        let claimed = match tokio::task::spawn_blocking(move || claim_due(&claim_state, config.batch_size, timeout * 2)).await {
+            Ok(Ok(claimed)) => claimed,
+            Ok(Err(e)) => {
+                log::debug!("Unable to claim webhook deliveries: {:#}", e);
+                continue;
+            },
+            Err(e) => {
+                log::error!("Webhook claim task panicked: {}", e);
+                continue;
+            },
+        };
+
+        let mut attempts = JoinSet::new();
+        for delivery in claimed {
+            let state = state.clone();
+            let client = client.clone();
+            attempts.spawn(async move {
+                let outcome = attempt(&client, &delivery).await;
+                let id = delivery.id;
+                let recorded = tokio::task::spawn_blocking(move || record_attempt(&state, &delivery, outcome)).await;
+                if let Ok(Err(e)) = recorded {
+                    log::error!("Failed to record webhook delivery {}: {:#}", id, e);
+                }
+            });
+        }
+        while attempts.join_next().await.is_some() {}
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Shared secret for X-Hbd-Signature, at least 16 characters
+    pub secret: String,
+    pub event_types: Vec<EventKind>,
+    /// Defaults to the caller's account; admin tokens may omit it to receive every account's events
+    pub account_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryQuery {
+    pub status: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+fn find_webhook(conn: &mut mysql::PooledConn, principal: &Principal, id: u64) -> Result<Webhook, ApiError> {
+    let row = conn.exec_first(format!("SELECT {} FROM webhooks WHERE id = ?", WEBHOOK_COLUMNS), (id,))
+        .map_err(|e| ApiError::database("Failed to get webhook", e))?;
+    row.map(webhook_from_row)
+        .filter(|hook| principal.can_access(hook.account_id))
+        .ok_or_else(|| ApiError::not_found(format!("Webhook {} not found", id)))
+}
+
+fn reload_registry(state: &AppState, conn: &mut mysql::PooledConn) {
+    if let Err(e) = state.webhooks.reload(conn) {
+        log::warn!("Failed to reload webhooks: {}", e);
+    }
+}
+
+/// Register a webhook
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<Webhook>), ApiError> {
+    if !(payload.url.starts_with("http://") || payload.url.starts_with("https://")) {
+        return Err(ApiError::bad_request("url must be an http(s) URL"));
+    }
+    if payload.secret.len() < MIN_SECRET_LENGTH {
+        return Err(ApiError::bad_request(format!("secret must be at least {} characters", MIN_SECRET_LENGTH)));
+    }
+    if payload.event_types.is_empty() {
+        return Err(ApiError::bad_request("event_types cannot be empty"));
+    }
+    let account_id = payload.account_id.or(principal.account_id);
+    principal.authorize(account_id)?;
+
+    let event_types: Vec<&str> = payload.event_types.iter().map(EventKind::as_str).collect();
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    conn.exec_drop(
+        "INSERT INTO webhooks (account_id, url, secret, event_types) VALUES (?, ?, ?, ?)",
+        (account_id, &payload.url, &payload.secret, event_types.join(",")),
+    ).map_err(|e| ApiError::database("Failed to create webhook", e))?;
+    let id = conn.last_insert_id();
+    reload_registry(&state, &mut conn);
+
+    log::info!("Webhook {} for account {:?} registered: {} {:?}", id, account_id, payload.url, event_types);
+    find_webhook(&mut conn, &principal, id).map(|hook| (StatusCode::CREATED, Json(hook)))
+}
+
+/// List the caller's webhooks
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let hooks: Vec<Webhook> = match principal.account_id {
+        Some(account) => conn.exec_map(
+            format!("SELECT {} FROM webhooks WHERE account_id = ? ORDER BY id", WEBHOOK_COLUMNS),
+            (account,),
+            webhook_from_row,
+        ),
+        None => conn.query_map(format!("SELECT {} FROM webhooks ORDER BY id", WEBHOOK_COLUMNS), webhook_from_row),
+    }.map_err(|e| ApiError::database("Failed to list webhooks", e))?;
+    Ok(Json(serde_json::json!({ "webhooks": hooks })))
+}
+
+/// Get a webhook
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u64>,
+) -> Result<Json<Webhook>, ApiError> {
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_webhook(&mut conn, &principal, id).map(Json)
+}
+
+/// Delete a webhook and dead-letter its pending deliveries
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_webhook(&mut conn, &principal, id)?;
+    let mut tx = conn.start_transaction(TxOpts::default())
+        .map_err(|e| ApiError::database("Failed to delete webhook", e))?;
+    tx.exec_drop(
+        "UPDATE webhook_deliveries SET status = 'dead', last_error = ? WHERE webhook_id = ? AND status = 'pending'",
+        (WEBHOOK_DELETED, id),
+    ).and_then(|_| tx.exec_drop("DELETE FROM webhooks WHERE id = ?", (id,)))
+        .and_then(|_| tx.commit())
+        .map_err(|e| ApiError::database("Failed to delete webhook", e))?;
+    reload_registry(&state, &mut conn);
+    log::info!("Webhook {} deleted", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Delivery log for a webhook, newest first (?status=pending|delivered|dead)
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<u64>,
+    Query(params): Query<DeliveryQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = params.offset.unwrap_or(0);
+    if let Some(status) = &params.status
+        && !["pending", "delivered", "dead"].contains(&status.as_str())
+    {
+        return Err(ApiError::bad_request("status must be pending, delivered or dead"));
+    }
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_webhook(&mut conn, &principal, id)?;
+    let rows: Vec<mysql::Row> = conn.exec(
+        format!(
+            "SELECT {} FROM webhook_deliveries WHERE webhook_id = ? AND (? IS NULL OR status = ?)
+             ORDER BY id DESC LIMIT ? OFFSET ?",
+            DELIVERY_COLUMNS
+        ),
+        (id, &params.status, &params.status, limit, offset),
+    ).map_err(|e| ApiError::database("Failed to list webhook deliveries", e))?;
+    let deliveries: Vec<WebhookDelivery> = rows.into_iter().map(delivery_from_row).collect();
+    Ok(Json(serde_json::json!({
+        "deliveries": deliveries,
+        "limit": limit,
+        "offset": offset
+    })))
+}
+
+/// Requeue a dead-lettered delivery with a fresh set of attempts
+pub async fn retry_delivery(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(delivery_id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    let webhook_id: Option<u64> = conn.exec_first("SELECT webhook_id FROM webhook_deliveries WHERE id = ?", (delivery_id,))
+        .map_err(|e| ApiError::database("Failed to get webhook delivery", e))?;
+    let not_found = || ApiError::not_found(format!("Delivery {} not found", delivery_id));
+    find_webhook(&mut conn, &principal, webhook_id.ok_or_else(not_found)?).map_err(|_| not_found())?;
+
+    conn.exec_drop(
+        "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, next_attempt_at = UTC_TIMESTAMP()
+         WHERE id = ? AND status = 'dead'",
+        (delivery_id,),
+    ).map_err(|e| ApiError::database("Failed to requeue webhook delivery", e))?;
+    if conn.affected_rows() == 0 {
+        return Err(ApiError::new(StatusCode::CONFLICT, "conflict", "Only dead-lettered deliveries can be retried"));
+    }
+    state.webhooks.wake();
+    log::info!("Webhook delivery {} requeued", delivery_id);
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            sign("topsecret", 1_700_000_000, r#"{"kind":"online"}"#),
+            "sha256=04509613b3040d52d5b6b5772aadf471db271c928f0aad116988063963f14da5"
+        );
+    }
+
+    #[test]
+    fn test_matches_account_and_event_type() {
+        let hook = Webhook {
+            id: 1,
+            account_id: Some(7),
+            url: "https://example.com/hook".to_string(),
+            secret: String::new(),
+            event_types: vec![EventKind::Offline],
+            created_at: None,
+        };
+        let event = |kind, account| DeviceEvent::device(kind, "AA", account, None, None, Utc::now());
+        assert!(hook.matches(&event(EventKind::Offline, Some(7))));
+        assert!(!hook.matches(&event(EventKind::Offline, Some(8))));
+        assert!(!hook.matches(&event(EventKind::Online, Some(7))));
+
+        let fleet_wide = Webhook { account_id: None, ..hook };
+        assert!(fleet_wide.matches(&event(EventKind::Offline, None)));
+    }
+
+    #[tokio::test]
+    async fn test_deleted_webhook_is_not_attempted() {
+        let delivery = ClaimedDelivery {
+            id: 1,
+            event_kind: "offline".to_string(),
+            payload: "{}".to_string(),
+            attempts: 0,
+            target: None,
+        };
+        let client = reqwest::Client::new();
+        assert_eq!(attempt(&client, &delivery).await, AttemptOutcome::WebhookDeleted);
+
+        // Nothing listens on port 1, so the attempt fails and is retried rather than dead-lettered
+        let delivery = ClaimedDelivery {
+            target: Some(("http://127.0.0.1:1/hook".to_string(), "topsecret".to_string())),
+            ..delivery
+        };
+        assert!(matches!(attempt(&client, &delivery).await, AttemptOutcome::Failed(None, _)));
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::events::DeviceEvent;
+use crate::journal::Journal;
+use crate::telemetry::Telemetry;
+
+/// A database write that could not be applied because MySQL was unavailable
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PendingWrite {
+    /// Heartbeat for a known device (set_device_last_heartbeat)
+    Heartbeat {
+        mac_address: String,
+        local_ip_address: String,
+        global_ip_address: String,
+        received_at: DateTime<Utc>,
+        /// Telemetry to store, when it changed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        telemetry: Option<Telemetry>,
+        /// Interval returned to the device, stored as devices.last_interval
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        interval: Option<u64>,
+    },
+    /// First heartbeat from an uninitialized device (set_ready_device)
+    ReadyDevice {
+        mac_address: String,
+        local_ip_address: String,
+        global_ip_address: String,
+        received_at: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        telemetry: Option<Telemetry>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        interval: Option<u64>,
+    },
+    /// Event for the outbox that was not part of a device write
+    Event {
+        event: DeviceEvent,
+    },
+    /// Firmware offered to a device by a campaign
+    FirmwareOffer {
+        campaign_id: u64,
+        mac_address: String,
+        previous_version: String,
+        offered_at: DateTime<Utc>,
+    },
+    /// A device came back on its campaign's firmware version
+    FirmwareUpgrade {
+        campaign_id: u64,
+        mac_address: String,
+        upgraded_at: DateTime<Utc>,
+    },
+}
+
+impl PendingWrite {
+    pub fn mac_address(&self) -> &str {
+        match self {
+            PendingWrite::Heartbeat { mac_address, .. } => mac_address,
+            PendingWrite::ReadyDevice { mac_address, .. } => mac_address,
+            PendingWrite::Event { event } => event.mac_address.as_deref().unwrap_or("-"),
+            PendingWrite::FirmwareOffer { mac_address, .. } => mac_address,
+            PendingWrite::FirmwareUpgrade { mac_address, .. } => mac_address,
+        }
+    }
+}
+
+/// A queued write and its position in the on-disk journal, if journaled
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedWrite {
+    pub journal_seq: Option<u64>,
+    pub write: PendingWrite,
+}
+
+#[derive(Debug, Default)]
+struct QueueInner {
+    items: VecDeque<QueuedWrite>,
+    /// First journaled write kept only on disk because the queue was full
+    spilled_from: Option<u64>,
+    /// Highest journal sequence read back by refill
+    loaded_through: u64,
+}
+
+/// Bounded in-memory FIFO of writes waiting for the database to come back.
+/// Journaled writes that do not fit stay on disk and are read back in order
+/// as the queue drains, so the journal only ever acknowledges applied writes.
+#[derive(Debug)]
+pub struct WriteQueue {
+    inner: Mutex<QueueInner>,
+    capacity: usize,
+}
+
+impl WriteQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(QueueInner::default()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Queue a write. When the queue is full a journaled write is left in the journal,
+    /// along with every journaled write after it; without a journal the oldest
+    /// unjournaled write is dropped.
+    pub fn push(&self, write: PendingWrite, journal_seq: Option<u64>) {
+        let mut inner = self.inner.lock().unwrap();
+        let full = inner.items.len() >= self.capacity;
+        if let Some(seq) = journal_seq {
+            if seq <= inner.loaded_through {
+                // Appended before a refill read it back, already queued
+                return;
+            }
+            if full || inner.spilled_from.is_some() {
+                if inner.spilled_from.is_none() {
+                    log::warn!("Write queue full ({}), keeping further journaled writes on disk", self.capacity);
+                }
+                inner.spilled_from = Some(inner.spilled_from.map_or(seq, |from| from.min(seq)));
+                return;
+            }
+        } else if full {
+            // Dropping a journaled write would let a later acknowledgement skip it
+            match inner.items.iter().position(|queued| queued.journal_seq.is_none()) {
+                Some(index) => {
+                    let dropped = inner.items.remove(index).expect("index is in range");
+                    log::warn!("Write queue full ({}), dropping oldest write for {}",
+                        self.capacity, dropped.write.mac_address());
+                },
+                None => {
+                    log::warn!("Write queue full ({}), dropping write for {}", self.capacity, write.mac_address());
+                    return;
+                },
+            }
+        }
+        inner.items.push_back(QueuedWrite { journal_seq, write });
+    }
+
+    /// Take the oldest queued write
+    pub fn pop(&self) -> Option<QueuedWrite> {
+        self.inner.lock().unwrap().items.pop_front()
+    }
+
+    /// Put a write back at the head of the queue after a failed replay
+    pub fn push_front(&self, write: QueuedWrite) {
+        self.inner.lock().unwrap().items.push_front(write);
+    }
+
+    /// Once the queue has drained, read back up to a queue's worth of writes that
+    /// were left in the journal. Returns how many were queued.
+    pub fn refill(&self, journal: &Journal) -> anyhow::Result<usize> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(from) = inner.spilled_from else { return Ok(0) };
+        if !inner.items.is_empty() {
+            return Ok(0);
+        }
+        let records = journal.read_from(from, self.capacity)?;
+        inner.spilled_from = match records.last() {
+            Some(last) if records.len() >= self.capacity => Some(last.seq + 1),
+            _ => None,
+        };
+        if let Some(last) = records.last() {
+            inner.loaded_through = last.seq;
+        }
+        let count = records.len();
+        inner.items.extend(records.into_iter()
+            .map(|record| QueuedWrite { journal_seq: Some(record.seq), write: record.write }));
+        Ok(count)
+    }
+
+    /// Writes queued in memory, not counting those left in the journal
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.items.is_empty() && inner.spilled_from.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(mac: &str) -> PendingWrite {
+        PendingWrite::Heartbeat {
+            mac_address: mac.to_string(),
+            local_ip_address: "192.168.1.10".to_string(),
+            global_ip_address: "203.0.113.5".to_string(),
+            received_at: Utc::now(),
+            telemetry: None,
+            interval: None,
+        }
+    }
+
+    #[test]
+    fn test_fifo_order_and_capacity() {
+        let queue = WriteQueue::new(2);
+        queue.push(heartbeat("AA"), None);
+        queue.push(heartbeat("BB"), Some(2));
+        queue.push(heartbeat("CC"), None);
+        assert_eq!(queue.len(), 2);
+        let first = queue.pop().unwrap();
+        assert_eq!(first.write.mac_address(), "BB");
+        assert_eq!(first.journal_seq, Some(2));
+        assert_eq!(queue.pop().unwrap().write.mac_address(), "CC");
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_overflow_stays_in_journal() {
+        let dir = std::env::temp_dir().join(format!("hbd-write-queue-overflow-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (journal, _) = Journal::open(&dir, 1024 * 1024, false).unwrap();
+        let queue = WriteQueue::new(2);
+        let macs = ["AA", "BB", "CC", "DD", "EE"];
+        for mac in macs {
+            let seq = journal.append(&heartbeat(mac)).unwrap();
+            queue.push(heartbeat(mac), Some(seq));
+        }
+        assert_eq!(queue.len(), 2);
+
+        // Replay everything in order, reading the overflow back from the journal
+        let mut replayed = Vec::new();
+        loop {
+            match queue.pop() {
+                Some(queued) => replayed.push((queued.write.mac_address().to_string(), queued.journal_seq.unwrap())),
+                None if queue.refill(&journal).unwrap() > 0 => continue,
+                None => break,
+            }
+        }
+        assert_eq!(replayed.iter().map(|(mac, _)| mac.as_str()).collect::<Vec<_>>(), macs);
+        assert!(queue.is_empty());
+
+        // A write journaled before the refill read it back is not queued twice
+        queue.push(heartbeat("EE"), Some(replayed[4].1));
+        assert!(queue.is_empty());
+        journal.acknowledge(replayed[4].1).unwrap();
+        assert_eq!(journal.pending_count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_event_write_round_trips() {
+        let event = DeviceEvent::device(crate::events::EventKind::Offline, "AA", Some(3), Some(5), None, Utc::now());
+        let write = PendingWrite::Event { event };
+        let json = serde_json::to_string(&write).unwrap();
+        assert!(json.starts_with(r#"{"kind":"event","#));
+        assert_eq!(serde_json::from_str::<PendingWrite>(&json).unwrap(), write);
+        assert_eq!(write.mac_address(), "AA");
+    }
+}
@@ -0,0 +1,419 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use mysql::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::auth::Principal;
+use crate::error::ApiError;
+use crate::server::{AppState, DeviceInfo};
+
+/// MySQL error code for duplicate unique keys
+const ER_DUP_ENTRY: u16 = 1062;
+
+const ZONE_COLUMNS: &str = "zone_number, account_id, name, site, expected_camera_count,
+    DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s'), heartbeat_interval";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Zone {
+    pub zone_number: i32,
+    pub account_id: Option<i32>,
+    pub name: String,
+    pub site: Option<String>,
+    pub expected_camera_count: Option<u32>,
+    pub created_at: Option<String>,
+    /// Expected heartbeat interval in seconds for cameras without their own
+    pub heartbeat_interval: Option<u32>,
+}
+
+type ZoneRow = (i32, Option<i32>, String, Option<String>, Option<u32>, Option<String>, Option<u32>);
+
+fn zone_from_row((zone_number, account_id, name, site, expected_camera_count, created_at, heartbeat_interval): ZoneRow) -> Zone {
+    Zone { zone_number, account_id, name, site, expected_camera_count, created_at, heartbeat_interval }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateZoneRequest {
+    pub zone_number: i32,
+    pub name: String,
+    pub site: Option<String>,
+    pub expected_camera_count: Option<u32>,
+    /// Account the zone belongs to, None for zones shared by the fleet
+    pub account_id: Option<i32>,
+    pub heartbeat_interval: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateZoneRequest {
+    pub name: Option<String>,
+    pub site: Option<String>,
+    pub expected_camera_count: Option<u32>,
+    pub heartbeat_interval: Option<u32>,
+}
+
+/// Live status of one camera in a zone
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CameraStatus {
+    pub camera_number: Option<i32>,
+    pub mac_address: String,
+    pub local_ip_address: Option<String>,
+    pub active: bool,
+    pub online: bool,
+    pub last_heartbeat: Option<DateTime<Utc>>,
+}
+
+/// Several cameras in one zone sharing a camera number
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CameraConflict {
+    pub camera_number: i32,
+    pub mac_addresses: Vec<String>,
+}
+
+/// A zone with its cameras. Zones referenced by devices but never registered
+/// are reported with `registered: false` and no metadata.
+#[derive(Debug, Clone, Serialize)]
+pub struct ZoneTopology {
+    pub zone_number: i32,
+    pub registered: bool,
+    pub zone: Option<Zone>,
+    pub camera_count: usize,
+    pub online_count: usize,
+    /// Expected cameras not registered in the zone, if an expected count is set
+    pub missing_cameras: Option<u32>,
+    pub conflicts: Vec<CameraConflict>,
+    pub cameras: Vec<CameraStatus>,
+}
+
+/// Camera numbers used by more than one device in the same zone
+pub fn find_conflicts(cameras: &[CameraStatus]) -> Vec<CameraConflict> {
+    let mut by_number: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+    for camera in cameras {
+        if let Some(number) = camera.camera_number {
+            by_number.entry(number).or_default().push(camera.mac_address.clone());
+        }
+    }
+    by_number.into_iter()
+        .filter(|(_, macs)| macs.len() > 1)
+        .map(|(camera_number, mac_addresses)| CameraConflict { camera_number, mac_addresses })
+        .collect()
+}
+
+/// Group cameras under their zones. `last_heartbeat` is the freshest known heartbeat
+/// for a MAC (the cache, falling back to MySQL) and how long it counts; a camera is
+/// online if that heartbeat is within that long of `now`.
+pub fn build_topology(
+    zones: Vec<Zone>,
+    devices: Vec<DeviceInfo>,
+    last_heartbeat: impl Fn(&DeviceInfo) -> Option<(DateTime<Utc>, ChronoDuration)>,
+    now: DateTime<Utc>,
+) -> Vec<ZoneTopology> {
+    let mut cameras: BTreeMap<i32, Vec<CameraStatus>> =
+        zones.iter().map(|zone| (zone.zone_number, Vec::new())).collect();
+    for device in &devices {
+        let Some(zone_number) = device.zone_number else { continue };
+        let seen = last_heartbeat(device);
+        cameras.entry(zone_number).or_default().push(CameraStatus {
+            camera_number: device.camera_number,
+            mac_address: device.mac_address.clone(),
+            local_ip_address: device.local_ip_address.clone(),
+            active: device.active,
+            online: device.active && seen.is_some_and(|(seen, stale_after)| now - seen < stale_after),
+            last_heartbeat: seen.map(|(seen, _)| seen),
+        });
+    }
+
+    let mut zones: BTreeMap<i32, Zone> = zones.into_iter().map(|zone| (zone.zone_number, zone)).collect();
+    cameras.into_iter().map(|(zone_number, mut cameras)| {
+        cameras.sort_by_key(|camera| (camera.camera_number, camera.mac_address.clone()));
+        let zone = zones.remove(&zone_number);
+        let camera_count = cameras.len();
+        ZoneTopology {
+            zone_number,
+            registered: zone.is_some(),
+            missing_cameras: zone.as_ref()
+                .and_then(|zone| zone.expected_camera_count)
+                .map(|expected| expected.saturating_sub(camera_count as u32)),
+            zone,
+            camera_count,
+            online_count: cameras.iter().filter(|camera| camera.online).count(),
+            conflicts: find_conflicts(&cameras),
+            cameras,
+        }
+    }).collect()
+}
+
+/// Freshest heartbeat for a device: the cache if it has seen the device, otherwise MySQL
+fn live_heartbeat(state: &AppState, device: &DeviceInfo, zone_interval: Option<u32>) -> Option<(DateTime<Utc>, ChronoDuration)> {
+    let cached = state.heart_beat_cache.get_device(&device.mac_address)
+        .map(|cached| (cached.last_heartbeat, Some(cached.interval)));
+    let (seen, interval) = cached.or_else(|| {
+        let seen = device.last_heartbeat_at?;
+        Some((seen, device.interval.or(device.heartbeat_interval.or(zone_interval).map(u64::from))))
+    })?;
+    let stale_after = crate::intervals::stale_after(interval, state.config.history.offline_after);
+    Some((seen, ChronoDuration::seconds(stale_after as i64)))
+}
+
+fn find_zone(conn: &mut mysql::PooledConn, zone_number: i32) -> mysql::Result<Option<Zone>> {
+    let row = conn.exec_first(format!("SELECT {} FROM zones WHERE zone_number = ?", ZONE_COLUMNS), (zone_number,))?;
+    Ok(row.map(zone_from_row))
+}
+
+/// Topology for the zones visible to the caller, optionally a single zone
+fn load_topology(state: &AppState, principal: &Principal, zone_number: Option<i32>) -> Result<Vec<ZoneTopology>, ApiError> {
+    let mut zone_filters = Vec::new();
+    let mut device_filters = vec!["zone_number IS NOT NULL"];
+    let mut values: Vec<mysql::Value> = Vec::new();
+    if let Some(zone) = zone_number {
+        zone_filters.push("zone_number = ?");
+        device_filters.push("zone_number = ?");
+        values.push(zone.into());
+    }
+    if let Some(account) = principal.account_id {
+        zone_filters.push("account_id = ?");
+        device_filters.push("account_id = ?");
+        values.push(account.into());
+    }
+    let zone_where = if zone_filters.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", zone_filters.join(" AND "))
+    };
+
+    let mut conn = state.get_read_connection().map_err(|_| ApiError::database_unavailable())?;
+    let zones: Vec<Zone> = conn.exec_map(
+        format!("SELECT {} FROM zones {} ORDER BY zone_number", ZONE_COLUMNS, zone_where),
+        values.clone(),
+        zone_from_row,
+    ).map_err(|e| ApiError::database("Failed to list zones", e))?;
+    let devices = crate::devices::query_devices(
+        &mut conn,
+        &format!("WHERE {}", device_filters.join(" AND ")),
+        values,
+        state.config.history.offline_after,
+    ).map_err(|e| ApiError::database("Failed to list zone cameras", e))?;
+
+    let zone_intervals: BTreeMap<i32, u32> = zones.iter()
+        .filter_map(|zone| Some((zone.zone_number, zone.heartbeat_interval?)))
+        .collect();
+    Ok(build_topology(
+        zones,
+        devices,
+        |device| live_heartbeat(state, device, device.zone_number.and_then(|zone| zone_intervals.get(&zone).copied())),
+        Utc::now(),
+    ))
+}
+
+/// Every visible zone with its cameras' live status
+pub async fn get_topology(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let zones = load_topology(&state, &principal, None)?;
+    Ok(Json(serde_json::json!({ "zones": zones })))
+}
+
+/// One zone with its cameras' live status
+pub async fn get_zone(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(zone_number): Path<i32>,
+) -> Result<Json<ZoneTopology>, ApiError> {
+    load_topology(&state, &principal, Some(zone_number))?
+        .pop()
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Zone {} not found", zone_number)))
+}
+
+/// Check a new zone. Zone numbers are shared by the whole fleet, so only fleet-wide
+/// callers register zones, assigning them to an account if they like.
+fn validate_create(principal: &Principal, payload: &CreateZoneRequest) -> Result<(), ApiError> {
+    principal.require_global()?;
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::bad_request("name is required"));
+    }
+    if payload.heartbeat_interval == Some(0) {
+        return Err(ApiError::bad_request("heartbeat_interval must be positive"));
+    }
+    Ok(())
+}
+
+/// Register a zone
+pub async fn create_zone(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<CreateZoneRequest>,
+) -> Result<(StatusCode, Json<Zone>), ApiError> {
+    validate_create(&principal, &payload)?;
+    let account_id = payload.account_id;
+
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    let result = conn.exec_drop(
+        "INSERT INTO zones (zone_number, account_id, name, site, expected_camera_count, heartbeat_interval)
+         VALUES (?, ?, ?, ?, ?, ?)",
+        (payload.zone_number, account_id, payload.name.trim(), &payload.site, payload.expected_camera_count,
+         payload.heartbeat_interval),
+    );
+    match result {
+        Err(mysql::Error::MySqlError(e)) if e.code == ER_DUP_ENTRY => {
+            return Err(ApiError::new(StatusCode::CONFLICT, "conflict", format!("Zone {} already exists", payload.zone_number)));
+        },
+        Err(e) => return Err(ApiError::database("Failed to create zone", e)),
+        Ok(()) => {},
+    }
+
+    if payload.heartbeat_interval.is_some() {
+        invalidate_cached_cameras(&state, payload.zone_number);
+    }
+    log::info!("Zone {} '{}' registered to account {:?}", payload.zone_number, payload.name, account_id);
+    find_zone(&mut conn, payload.zone_number)
+        .map_err(|e| ApiError::database("Failed to read created zone", e))?
+        .map(|zone| (StatusCode::CREATED, Json(zone)))
+        .ok_or_else(|| ApiError::not_found(format!("Zone {} not found", payload.zone_number)))
+}
+
+/// Fetch a zone the caller may modify
+fn find_visible_zone(conn: &mut mysql::PooledConn, principal: &Principal, zone_number: i32) -> Result<Zone, ApiError> {
+    find_zone(conn, zone_number)
+        .map_err(|e| ApiError::database("Failed to get zone", e))?
+        .filter(|zone| principal.can_access(zone.account_id))
+        .ok_or_else(|| ApiError::not_found(format!("Zone {} not found", zone_number)))
+}
+
+/// Drop a zone's cameras from the heartbeat cache so their next heartbeat picks up
+/// the zone's heartbeat interval
+fn invalidate_cached_cameras(state: &AppState, zone_number: i32) {
+    for device in state.heart_beat_cache.devices() {
+        if device.zone_number == Some(zone_number) {
+            state.heart_beat_cache.remove_device(&device.mac_address);
+        }
+    }
+}
+
+/// Update a zone's name, site, expected camera count or heartbeat interval
+pub async fn update_zone(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(zone_number): Path<i32>,
+    Json(payload): Json<UpdateZoneRequest>,
+) -> Result<Json<Zone>, ApiError> {
+    if payload.heartbeat_interval == Some(0) {
+        return Err(ApiError::bad_request("heartbeat_interval must be positive"));
+    }
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_visible_zone(&mut conn, &principal, zone_number)?;
+    conn.exec_drop(
+        "UPDATE zones SET
+            name = COALESCE(?, name),
+            site = COALESCE(?, site),
+            expected_camera_count = COALESCE(?, expected_camera_count),
+            heartbeat_interval = COALESCE(?, heartbeat_interval)
+         WHERE zone_number = ?",
+        (&payload.name, &payload.site, payload.expected_camera_count, payload.heartbeat_interval, zone_number),
+    ).map_err(|e| ApiError::database("Failed to update zone", e))?;
+    if payload.heartbeat_interval.is_some() {
+        invalidate_cached_cameras(&state, zone_number);
+    }
+    find_visible_zone(&mut conn, &principal, zone_number).map(Json)
+}
+
+/// Remove a zone's metadata; its cameras keep their zone_number
+pub async fn delete_zone(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Path(zone_number): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    let mut conn = state.get_connection().map_err(|_| ApiError::database_unavailable())?;
+    find_visible_zone(&mut conn, &principal, zone_number)?;
+    conn.exec_drop("DELETE FROM zones WHERE zone_number = ?", (zone_number,))
+        .map_err(|e| ApiError::database("Failed to delete zone", e))?;
+    invalidate_cached_cameras(&state, zone_number);
+    log::info!("Zone {} deleted", zone_number);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn device(mac: &str, zone: i32, camera: i32) -> DeviceInfo {
+        DeviceInfo {
+            id: 1,
+            mac_address: mac.to_string(),
+            local_ip_address: None,
+            global_ip_address: None,
+            last_heartbeat: None,
+            camera_number: Some(camera),
+            zone_number: Some(zone),
+            created_at: None,
+            last_modified: None,
+            account_id: None,
+            active: true,
+            online: false,
+            flapping: false,
+            heartbeat_interval: None,
+            interval: None,
+            last_heartbeat_at: None,
+        }
+    }
+
+    #[test]
+    fn test_topology_groups_cameras_and_flags_conflicts() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap();
+        let zones = vec![Zone {
+            zone_number: 1,
+            account_id: None,
+            name: "Lobby".to_string(),
+            site: None,
+            expected_camera_count: Some(4),
+            created_at: None,
+            heartbeat_interval: None,
+        }];
+        let devices = vec![device("AA", 1, 1), device("BB", 1, 1), device("CC", 1, 2), device("DD", 9, 1)];
+        let topology = build_topology(
+            zones,
+            devices,
+            |device| (device.mac_address == "AA").then(|| (now - ChronoDuration::seconds(30), ChronoDuration::minutes(10))),
+            now,
+        );
+
+        assert_eq!(topology.len(), 2);
+        let lobby = &topology[0];
+        assert!(lobby.registered);
+        assert_eq!(lobby.camera_count, 3);
+        assert_eq!(lobby.online_count, 1);
+        assert_eq!(lobby.missing_cameras, Some(1));
+        assert_eq!(lobby.conflicts, vec![CameraConflict {
+            camera_number: 1,
+            mac_addresses: vec!["AA".to_string(), "BB".to_string()],
+        }]);
+
+        // Zones only referenced by devices are still reported
+        assert_eq!(topology[1].zone_number, 9);
+        assert!(!topology[1].registered);
+    }
+
+    #[test]
+    fn test_only_fleet_wide_callers_register_zones() {
+        let zone = |name: &str, account_id| CreateZoneRequest {
+            zone_number: 1,
+            name: name.to_string(),
+            site: None,
+            expected_camera_count: None,
+            account_id,
+            heartbeat_interval: None,
+        };
+        let admin = Principal { account_id: None };
+        let tenant = Principal { account_id: Some(7) };
+        assert!(validate_create(&admin, &zone("Lobby", Some(7))).is_ok());
+        assert_eq!(validate_create(&admin, &zone(" ", None)).unwrap_err().status, StatusCode::BAD_REQUEST);
+        // Zone numbers are global, so a tenant could otherwise probe other tenants' numbers
+        assert_eq!(validate_create(&tenant, &zone("Lobby", Some(7))).unwrap_err().status, StatusCode::FORBIDDEN);
+    }
+}